@@ -0,0 +1,122 @@
+use crate::command_exists::{command_exists, find_command};
+use crate::compile_helpers;
+use crate::cross_target::{TargetOs, host_os};
+use crate::{LOG, run_command};
+use std::fs;
+use std::path::Path;
+
+/// Assemblers for Intel-syntax `.asm` files, in order of preference.
+pub const NASM_ASSEMBLERS: &[&str] = &["nasm", "yasm"];
+
+fn extension(src: &str) -> String {
+    Path::new(src)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// `.s` and `.S` files, which gcc and clang assemble (and preprocess, for `.S`) themselves.
+pub fn is_gas_source(src: &str) -> bool {
+    extension(src) == "s"
+}
+
+/// `.asm` files, assembled with nasm or yasm.
+pub fn is_nasm_source(src: &str) -> bool {
+    extension(src) == "asm"
+}
+
+pub fn find_assembler() -> Option<&'static str> {
+    NASM_ASSEMBLERS.iter().copied().find(|a| command_exists(a))
+}
+
+/// Whether the source exports `symbol` (`global main`, `.globl _start`, ...).
+fn exports(content: &str, symbol: &str) -> bool {
+    content.lines().any(|line| {
+        let mut words = line
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|w| !w.is_empty());
+        matches!(
+            words.next().map(|w| w.to_ascii_lowercase()).as_deref(),
+            Some("global" | ".global" | ".globl")
+        ) && words.any(|w| w == symbol)
+    })
+}
+
+/// A program that provides `_start` itself instead of `main` must not be
+/// linked against the C runtime's startup code.
+fn is_freestanding(src: &str) -> bool {
+    let content = fs::read_to_string(src).unwrap_or_default();
+    exports(&content, "_start") && !exports(&content, "main")
+}
+
+/// Link flags gcc and clang need for a `.s`/`.S` program: none for programs
+/// with a `main`, and no C runtime for ones that start at `_start`.
+pub fn gas_link_flags(src: &str) -> Option<&'static str> {
+    (host_os() == TargetOs::Linux && is_freestanding(src)).then_some("-nostdlib -static")
+}
+
+/// The nasm output format for this machine.
+fn object_format() -> &'static str {
+    match host_os() {
+        TargetOs::Windows => "win64",
+        TargetOs::MacOs => "macho64",
+        _ => "elf64",
+    }
+}
+
+/// Assembles `src` with nasm or yasm and links it into `exe`: with `ld` when
+/// the program starts at `_start` on Linux, otherwise through `compiler` so
+/// the C runtime (and `main`) are linked in. `extra` goes to the assembler.
+pub fn build_nasm(
+    compiler: &str,
+    src: &str,
+    exe: &str,
+    extra: &str,
+    link: &str,
+) -> Result<(), String> {
+    let assembler = find_assembler()
+        .ok_or("Building .asm files needs nasm or yasm; run crun -check for the install command")?;
+    let object = Path::new(exe).with_extension(if cfg!(windows) { "obj" } else { "o" });
+    let object = object.to_string_lossy().to_string();
+
+    let mut args = vec!["-f", object_format()];
+    args.extend(extra.split_whitespace());
+    args.extend(["-o", &object, src]);
+    LOG.lock().unwrap().println(
+        &format_args!(
+            "Using assembler: {} ({})",
+            assembler,
+            find_command(assembler)
+        ),
+        None,
+    );
+    if !run_command(assembler, &args) {
+        return Err("Assembly failed".to_string());
+    }
+
+    if host_os() == TargetOs::Linux && is_freestanding(src) {
+        let mut args = vec!["-o", exe, &object];
+        args.extend(link.split_whitespace());
+        if !run_command("ld", &args) {
+            return Err("Linking with ld failed".to_string());
+        }
+        return Ok(());
+    }
+    if compiler.is_empty() {
+        return Err("No C compiler found to link the program with".to_string());
+    }
+    // hand-written code usually uses absolute addresses, which PIE executables reject
+    let link = if host_os() == TargetOs::Linux {
+        format!("-no-pie {}", link)
+    } else {
+        link.to_string()
+    };
+    if !compile_helpers::compile(compiler, exe, &object, "", &[], &link, None).0 {
+        return Err(format!("Linking with {} failed", compiler));
+    }
+    Ok(())
+}
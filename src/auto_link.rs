@@ -0,0 +1,145 @@
+use crate::build_env::{BuildOption, OptionKind, Setting};
+use crate::capabilities;
+use crate::compile_helpers;
+use crate::compiler_registry::{self, Family, Lang};
+use crate::config;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Headers whose library the linker does not pick up by itself. `threads`
+/// stands for the compiler's thread flag (`-pthread`), anything else is
+/// linked like `--link`.
+const DEFAULT_LINKS: &[(&str, &str)] = &[
+    ("math.h", "m"),
+    ("pthread.h", "threads"),
+    ("threads.h", "threads"),
+];
+
+/// Whether `lang` goes through a C-style preprocessor and linker.
+fn scans(lang: Lang) -> bool {
+    matches!(lang, Lang::C | Lang::Cpp | Lang::ObjC)
+}
+
+/// `auto-link = false` in the `[defaults]` section of `crun.ini`; the last file read wins.
+fn disabled_in_config() -> bool {
+    config::sections("defaults")
+        .flat_map(|s| s.entries.iter())
+        .filter(|(key, _)| key == "auto-link")
+        .map(|(_, value)| matches!(value.as_str(), "false" | "off" | "no" | "none"))
+        .last()
+        .unwrap_or(false)
+}
+
+/// The header to libraries table: the built-in entries, overridden and
+/// extended by `header = lib, lib` lines in `[auto-link]` sections. An empty
+/// value (or `none`) turns a header off.
+fn mapping() -> Vec<(String, Vec<String>)> {
+    let mut table: Vec<(String, Vec<String>)> = DEFAULT_LINKS
+        .iter()
+        .map(|(header, lib)| (header.to_string(), vec![lib.to_string()]))
+        .collect();
+    for (header, value) in config::sections("auto-link").flat_map(|s| s.entries.iter()) {
+        let libs: Vec<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty() && *v != "none")
+            .map(String::from)
+            .collect();
+        table.retain(|(h, _)| h != header);
+        table.push((header.clone(), libs));
+    }
+    table
+}
+
+/// Headers included by `src`, following `#include "..."` into local headers.
+fn included_headers(src: &Path, seen: &mut HashSet<PathBuf>, headers: &mut Vec<String>) {
+    let Ok(path) = fs::canonicalize(src) else {
+        return;
+    };
+    if !seen.insert(path.clone()) {
+        return;
+    }
+    let content = fs::read_to_string(&path).unwrap_or_default();
+    for line in content.lines() {
+        let Some(rest) = line.trim_start().strip_prefix('#') else {
+            continue;
+        };
+        let Some(target) = rest.trim_start().strip_prefix("include") else {
+            continue;
+        };
+        let target = target.trim();
+        let (name, local) = match target.chars().next() {
+            Some('<') => (target[1..].split('>').next().unwrap_or(""), false),
+            Some('"') => (target[1..].split('"').next().unwrap_or(""), true),
+            _ => continue,
+        };
+        if !headers.iter().any(|h| h == name) {
+            headers.push(name.to_string());
+        }
+        if local && let Some(dir) = path.parent() {
+            included_headers(&dir.join(name), seen, headers);
+        }
+    }
+}
+
+/// Compile and link flags for the libraries `src`'s includes need with
+/// `compiler`, skipping ones `existing` flags already provide. Each flag's
+/// source names the include it came from.
+pub fn link_flags(
+    compiler: &str,
+    lang: Lang,
+    src: &str,
+    existing: &str,
+) -> (Vec<Setting>, Vec<Setting>) {
+    let mut compile = Vec::new();
+    let mut link = Vec::new();
+    if !scans(lang) || disabled_in_config() {
+        return (compile, link);
+    }
+    let family = compiler_registry::family_of(compiler);
+    let mut headers = Vec::new();
+    included_headers(Path::new(src), &mut HashSet::new(), &mut headers);
+    let mut added: Vec<String> = existing.split_whitespace().map(String::from).collect();
+
+    for (header, libs) in mapping() {
+        if !headers.contains(&header) {
+            continue;
+        }
+        let source = format!("#include <{}>", header);
+        for lib in libs {
+            let (list, settings) = match lib.as_str() {
+                "threads" => {
+                    // cl links the threading runtime by default
+                    let flag = capabilities::threads_flag(family).unwrap_or_default();
+                    let setting = Setting {
+                        value: flag.to_string(),
+                        source: source.clone(),
+                    };
+                    (&mut compile, vec![setting])
+                }
+                // the math functions are part of the C runtime for cl
+                "m" if family == Family::Msvc => continue,
+                _ => {
+                    let option = BuildOption {
+                        kind: OptionKind::Link,
+                        value: lib.clone(),
+                        source: source.clone(),
+                    };
+                    match compile_helpers::option_flags(compiler, &[option]) {
+                        Ok((_, settings)) => (&mut link, settings),
+                        Err(_) => continue,
+                    }
+                }
+            };
+            for setting in settings {
+                if setting.value.is_empty() || added.contains(&setting.value) {
+                    continue;
+                }
+                added.push(setting.value.clone());
+                list.push(setting);
+            }
+        }
+    }
+    (compile, link)
+}
@@ -0,0 +1,241 @@
+use crate::compiler_registry::Lang;
+use std::path::Path;
+
+/// Everything crun needs to know about a source language: which files belong
+/// to it, where the environment names its compiler and flags, and what
+/// `crun init` writes. Compiler candidates and their argument layout live in
+/// the compiler registry, keyed on `lang`.
+pub struct Backend {
+    pub lang: Lang,
+    /// Name used in messages, e.g. "C++".
+    pub name: &'static str,
+    /// Extensions without the dot, in the order `find_source` tries them.
+    pub extensions: &'static [&'static str],
+    /// Variable naming the compiler (`CC`), if the language has a convention for one.
+    pub compiler_var: Option<&'static str>,
+    /// Variable holding extra compile flags (`CFLAGS`).
+    pub flags_var: Option<&'static str>,
+    /// `crun init` templates by extension; the first one is the default.
+    pub templates: &'static [(&'static str, &'static str)],
+    /// Whether `-check` builds the template with every installed compiler.
+    pub doctor: bool,
+}
+
+impl Backend {
+    pub fn template(&self, ext: &str) -> &'static str {
+        self.templates
+            .iter()
+            .find(|(e, _)| e.eq_ignore_ascii_case(ext))
+            .unwrap_or(&self.templates[0])
+            .1
+    }
+}
+
+const C_TEMPLATE: &str = r#"#include <stdio.h>
+
+int main() {
+    printf("Hello from C!\n");
+    return 0;
+}
+"#;
+
+const CPP_TEMPLATE: &str = r#"#include <iostream>
+
+int main() {
+    std::cout << "Hello from C++!" << std::endl;
+    return 0;
+}
+"#;
+
+// x86-64 Linux: write(1, msg, len) then exit(0), no C runtime involved
+const NASM_TEMPLATE: &str = r#"; x86-64 Linux, assembled with nasm (or yasm) and linked with ld
+global _start
+
+section .data
+msg:    db "Hello from assembly!", 10
+len:    equ $ - msg
+
+section .text
+_start:
+    mov rax, 1          ; write(
+    mov rdi, 1          ;   stdout,
+    lea rsi, [rel msg]  ;   msg,
+    mov rdx, len        ;   len)
+    syscall
+
+    mov rax, 60         ; exit(
+    xor rdi, rdi        ;   0)
+    syscall
+
+section .note.GNU-stack noalloc noexec nowrite progbits
+"#;
+
+const GAS_TEMPLATE: &str = r#"# x86-64 Linux, AT&T syntax, assembled and linked by gcc or clang
+    .globl _start
+
+    .data
+msg:
+    .ascii "Hello from assembly!\n"
+    .set len, . - msg
+
+    .text
+_start:
+    mov $1, %rax        # write(
+    mov $1, %rdi        #   stdout,
+    lea msg(%rip), %rsi #   msg,
+    mov $len, %rdx      #   len)
+    syscall
+
+    mov $60, %rax       # exit(
+    xor %rdi, %rdi      #   0)
+    syscall
+
+    .section .note.GNU-stack, "", @progbits
+"#;
+
+// plain C is valid Objective-C and links without Foundation or an ObjC runtime
+const OBJC_TEMPLATE: &str = r#"#import <stdio.h>
+
+int main(void) {
+    printf("Hello from Objective-C!\n");
+    return 0;
+}
+"#;
+
+const FORTRAN_TEMPLATE: &str = r#"program hello
+    implicit none
+    print '(a)', 'Hello from Fortran!'
+end program hello
+"#;
+
+const ZIG_TEMPLATE: &str = r#"const std = @import("std");
+
+pub fn main() void {
+    std.debug.print("Hello from Zig!\n", .{});
+}
+"#;
+
+const RUST_TEMPLATE: &str = r#"fn main() {
+    println!("Hello from Rust!");
+}
+"#;
+
+const GO_TEMPLATE: &str = r#"package main
+
+import "fmt"
+
+func main() {
+	fmt.Println("Hello from Go!")
+}
+"#;
+
+const D_TEMPLATE: &str = r#"import std.stdio;
+
+void main() {
+    writeln("Hello from D!");
+}
+"#;
+
+/// All languages crun builds. Assembly is compiled as C, since gcc and clang
+/// assemble `.s`/`.S` files and `.asm` files only need a C compiler to link.
+pub static BACKENDS: &[Backend] = &[
+    Backend {
+        lang: Lang::C,
+        name: "C",
+        extensions: &["c"],
+        compiler_var: Some("CC"),
+        flags_var: Some("CFLAGS"),
+        templates: &[("c", C_TEMPLATE)],
+        doctor: true,
+    },
+    Backend {
+        lang: Lang::Cpp,
+        name: "C++",
+        extensions: &["cpp", "cc", "cxx"],
+        compiler_var: Some("CXX"),
+        flags_var: Some("CXXFLAGS"),
+        templates: &[("cpp", CPP_TEMPLATE)],
+        doctor: true,
+    },
+    Backend {
+        lang: Lang::C,
+        name: "assembly",
+        extensions: &["S", "s", "asm"],
+        compiler_var: Some("CC"),
+        flags_var: Some("CFLAGS"),
+        templates: &[("asm", NASM_TEMPLATE), ("s", GAS_TEMPLATE)],
+        doctor: false,
+    },
+    Backend {
+        lang: Lang::ObjC,
+        name: "Objective-C",
+        extensions: &["m"],
+        compiler_var: Some("OBJC"),
+        flags_var: Some("OBJCFLAGS"),
+        templates: &[("m", OBJC_TEMPLATE)],
+        doctor: true,
+    },
+    Backend {
+        lang: Lang::Fortran,
+        name: "Fortran",
+        extensions: &["f90", "f95", "f03", "f08", "f", "for"],
+        compiler_var: Some("FC"),
+        flags_var: Some("FFLAGS"),
+        templates: &[("f90", FORTRAN_TEMPLATE)],
+        doctor: true,
+    },
+    Backend {
+        lang: Lang::Zig,
+        name: "Zig",
+        extensions: &["zig"],
+        compiler_var: None,
+        flags_var: None,
+        templates: &[("zig", ZIG_TEMPLATE)],
+        doctor: true,
+    },
+    Backend {
+        lang: Lang::Rust,
+        name: "Rust",
+        extensions: &["rs"],
+        compiler_var: Some("RUSTC"),
+        flags_var: Some("RUSTFLAGS"),
+        templates: &[("rs", RUST_TEMPLATE)],
+        doctor: true,
+    },
+    Backend {
+        lang: Lang::Go,
+        name: "Go",
+        extensions: &["go"],
+        compiler_var: None,
+        // GOFLAGS is read by the go command itself
+        flags_var: None,
+        templates: &[("go", GO_TEMPLATE)],
+        doctor: true,
+    },
+    Backend {
+        lang: Lang::D,
+        name: "D",
+        extensions: &["d"],
+        compiler_var: Some("DC"),
+        flags_var: Some("DFLAGS"),
+        templates: &[("d", D_TEMPLATE)],
+        doctor: true,
+    },
+];
+
+/// The backend a file belongs to, going by its extension (case-insensitively,
+/// so `.F90` is Fortran).
+pub fn for_source(src: &str) -> Option<&'static Backend> {
+    let ext = Path::new(src).extension()?.to_str()?;
+    BACKENDS
+        .iter()
+        .find(|b| b.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+}
+
+/// The main backend of `lang` (C for C, not assembly).
+pub fn for_lang(lang: Lang) -> &'static Backend {
+    BACKENDS
+        .iter()
+        .find(|b| b.lang == lang)
+        .expect("every language has a backend")
+}
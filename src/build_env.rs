@@ -0,0 +1,190 @@
+use crate::backends;
+use crate::compiler_registry::Lang;
+use crate::config;
+use std::env;
+
+/// A setting together with where it came from, so `-v` can explain it.
+#[derive(Clone, Debug)]
+pub struct Setting {
+    pub value: String,
+    pub source: String,
+}
+
+impl Setting {
+    fn new(value: impl Into<String>, source: impl Into<String>) -> Self {
+        Setting {
+            value: value.into(),
+            source: source.into(),
+        }
+    }
+}
+
+/// Compiler-neutral build options, translated to each family's syntax by
+/// [`crate::compile_helpers::option_flags`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OptionKind {
+    Include,
+    Define,
+    LibDir,
+    Link,
+}
+
+impl OptionKind {
+    pub const ALL: [OptionKind; 4] = [
+        OptionKind::Include,
+        OptionKind::Define,
+        OptionKind::LibDir,
+        OptionKind::Link,
+    ];
+
+    pub fn flag(&self) -> &'static str {
+        match self {
+            OptionKind::Include => "-I",
+            OptionKind::Define => "-D",
+            OptionKind::LibDir => "--lib-dir",
+            OptionKind::Link => "--link",
+        }
+    }
+
+    /// The key used for this option in the `[defaults]` section of `crun.ini`.
+    fn config_key(&self) -> &'static str {
+        match self {
+            OptionKind::Include => "include",
+            OptionKind::Define => "define",
+            OptionKind::LibDir => "lib-dir",
+            OptionKind::Link => "link",
+        }
+    }
+
+    /// Library directories and libraries go after the sources.
+    pub fn is_link(&self) -> bool {
+        matches!(self, OptionKind::LibDir | OptionKind::Link)
+    }
+}
+
+/// One `-I`, `-D`, `--lib-dir` or `--link` value and where it came from.
+#[derive(Clone, Debug)]
+pub struct BuildOption {
+    pub kind: OptionKind,
+    pub value: String,
+    pub source: String,
+}
+
+/// Compiler and flags requested on the command line or through the
+/// environment, resolved for one source language.
+#[derive(Clone, Debug, Default)]
+pub struct BuildSettings {
+    /// Compiler spec to use instead of auto-detection.
+    pub compiler: Option<Setting>,
+    /// Flags placed where the compiler family expects `-e` flags.
+    pub compile_flags: Vec<Setting>,
+    /// Flags for the link step, after the source (behind `/link` for cl).
+    pub link_flags: Vec<Setting>,
+    /// Compiler cache (ccache, sccache) the compiler is launched through.
+    pub wrapper: Option<Setting>,
+    /// Include directories, defines and libraries, not yet in any compiler's syntax.
+    pub options: Vec<BuildOption>,
+}
+
+impl BuildSettings {
+    pub fn compile_flags_string(&self) -> String {
+        join(&self.compile_flags)
+    }
+
+    pub fn link_flags_string(&self) -> String {
+        join(&self.link_flags)
+    }
+}
+
+fn join(settings: &[Setting]) -> String {
+    settings
+        .iter()
+        .map(|s| s.value.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn read(var: &str) -> Option<Setting> {
+    let value = env::var(var).ok()?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| Setting::new(value, format!("${}", var)))
+}
+
+/// `include`, `define`, `lib-dir` and `link` entries from the `[defaults]`
+/// section of `crun.ini`; each may repeat or list several values separated by commas.
+fn configured_options() -> Vec<BuildOption> {
+    let mut options = Vec::new();
+    for (key, value) in config::sections("defaults").flat_map(|s| s.entries.iter()) {
+        let Some(kind) = OptionKind::ALL.into_iter().find(|k| k.config_key() == key) else {
+            continue;
+        };
+        options.extend(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(|v| BuildOption {
+                    kind,
+                    value: v.to_string(),
+                    source: "crun.ini".to_string(),
+                }),
+        );
+    }
+    options
+}
+
+/// Resolves the compiler and flags for `lang`. The compiler comes from the
+/// first of `-c`, `CRUN_COMPILER`, then the language's own variable (`CC`,
+/// `CXX`, `FC`, ...); `None` means auto-detect. `CRUN_FLAGS` stands in for
+/// `-e` when it is not given, and the language's flags variable (`CFLAGS`,
+/// `CXXFLAGS`, ...) and `LDFLAGS` are always added. Options from
+/// `crun.ini` come before the ones given on the command line.
+pub fn resolve(
+    cli_compiler: &str,
+    cli_extra: &str,
+    cli_options: &[(OptionKind, String)],
+    lang: Lang,
+) -> BuildSettings {
+    let mut settings = BuildSettings::default();
+    let backend = backends::for_lang(lang);
+
+    let compiler = if cli_compiler.is_empty() {
+        read("CRUN_COMPILER").or_else(|| backend.compiler_var.and_then(read))
+    } else {
+        Some(Setting::new(cli_compiler, "-c"))
+    };
+    // CC="gcc -m32" is common; the words after the compiler are flags
+    if let Some(compiler) = compiler {
+        let mut words = compiler.value.split_whitespace();
+        let name = words.next().unwrap_or_default().to_string();
+        let rest: Vec<&str> = words.collect();
+        if !rest.is_empty() && compiler.source != "-c" {
+            settings
+                .compile_flags
+                .push(Setting::new(rest.join(" "), compiler.source.clone()));
+            settings.compiler = Some(Setting::new(name, compiler.source));
+        } else {
+            settings.compiler = Some(compiler);
+        }
+    }
+
+    if let Some(flags) = backend.flags_var.and_then(read) {
+        settings.compile_flags.push(flags);
+    }
+    if cli_extra.is_empty() {
+        settings.compile_flags.extend(read("CRUN_FLAGS"));
+    } else {
+        settings.compile_flags.push(Setting::new(cli_extra, "-e"));
+    }
+    settings.link_flags.extend(read("LDFLAGS"));
+
+    settings.options = configured_options();
+    settings
+        .options
+        .extend(cli_options.iter().map(|(kind, value)| BuildOption {
+            kind: *kind,
+            value: value.clone(),
+            source: kind.flag().to_string(),
+        }));
+    settings
+}
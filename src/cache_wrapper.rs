@@ -0,0 +1,102 @@
+use crate::build_env::Setting;
+use crate::command_exists::{command_exists, find_command};
+use crate::compiler_registry::{self, Family};
+use crate::config;
+use std::path::Path;
+use std::process::Command;
+
+/// Wrappers tried when neither `--cache-wrapper` nor the config names one.
+const KNOWN_WRAPPERS: &[&str] = &["ccache", "sccache"];
+
+/// Families whose command lines ccache and sccache understand. cl-style
+/// drivers, zig (which caches by itself) and the rest are always run directly.
+fn supports_wrapper(family: Family) -> bool {
+    matches!(family, Family::Gcc | Family::Clang | Family::Intel)
+}
+
+/// `cache-wrapper` from the `[defaults]` section of `crun.ini`; the last file read wins.
+fn configured_wrapper() -> Option<String> {
+    config::sections("defaults")
+        .flat_map(|s| s.entries.iter())
+        .filter(|(key, _)| key == "cache-wrapper")
+        .map(|(_, value)| value.clone())
+        .last()
+}
+
+/// Picks the compiler cache to launch `compiler` through: `--cache-wrapper`,
+/// then `cache-wrapper` in `crun.ini`, then whichever of ccache and sccache is
+/// installed. `none` (or `off`) disables it.
+pub fn resolve(cli: &str, compiler: &str) -> Option<Setting> {
+    let (name, source) = if !cli.is_empty() {
+        (cli.to_string(), "--cache-wrapper")
+    } else if let Some(name) = configured_wrapper() {
+        (name, "crun.ini")
+    } else {
+        let name = KNOWN_WRAPPERS.iter().find(|w| command_exists(w))?;
+        (name.to_string(), "auto-detected")
+    };
+    if matches!(name.as_str(), "none" | "off" | "") {
+        return None;
+    }
+    if !supports_wrapper(compiler_registry::family_of(compiler)) {
+        return None;
+    }
+    // distro ccache symlinks (/usr/lib/ccache/gcc) already go through the cache
+    let resolved = compiler_registry::resolve_path(compiler);
+    let target = std::fs::canonicalize(&resolved).unwrap_or_default();
+    if target.file_stem().and_then(|s| s.to_str()) == Some("ccache") {
+        return None;
+    }
+    if !command_exists(&name) {
+        crate::LOG.lock().unwrap().println(
+            &format_args!("Cache wrapper '{}' not found, compiling without it", name),
+            None,
+        );
+        return None;
+    }
+    Some(Setting {
+        value: name,
+        source: source.to_string(),
+    })
+}
+
+/// Total cache hits and misses reported by the wrapper, read before and after
+/// a compile to tell whether it was served from the cache.
+pub fn stats(wrapper: &str) -> Option<(u64, u64)> {
+    let name = Path::new(&find_command(wrapper))
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| wrapper.to_string());
+    match name.as_str() {
+        "ccache" => {
+            let output = Command::new(wrapper).arg("--print-stats").output().ok()?;
+            let text = String::from_utf8_lossy(&output.stdout);
+            let value = |key: &str| -> u64 {
+                text.lines()
+                    .filter_map(|l| l.split_once('\t'))
+                    .find(|(k, _)| *k == key)
+                    .and_then(|(_, v)| v.trim().parse().ok())
+                    .unwrap_or(0)
+            };
+            output.status.success().then(|| {
+                (
+                    value("direct_cache_hit") + value("preprocessed_cache_hit"),
+                    value("cache_miss"),
+                )
+            })
+        }
+        "sccache" => {
+            let output = Command::new(wrapper).arg("--show-stats").output().ok()?;
+            let text = String::from_utf8_lossy(&output.stdout);
+            // "Cache hits                            3"
+            let value = |label: &str| -> Option<u64> {
+                text.lines()
+                    .find(|l| l.starts_with(label) && l[label.len()..].starts_with(' '))
+                    .and_then(|l| l.split_whitespace().last())
+                    .and_then(|v| v.parse().ok())
+            };
+            Some((value("Cache hits")?, value("Cache misses")?))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,293 @@
+use crate::compile_helpers::compile_quiet;
+use crate::compiler_registry::{Family, Lang, family_of};
+use crate::probe_cache;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{LazyLock, Mutex};
+
+/// Features a compiler was able to build and link, e.g. `std=c11`, `asan`,
+/// `threads`, `openmp`, `static` or `header=<filesystem>`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Capabilities {
+    pub features: Vec<String>,
+}
+
+impl Capabilities {
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    pub fn standards(&self) -> Vec<&str> {
+        self.features
+            .iter()
+            .filter_map(|f| f.strip_prefix("std="))
+            .collect()
+    }
+
+    pub fn headers(&self) -> Vec<&str> {
+        self.features
+            .iter()
+            .filter_map(|f| f.strip_prefix("header="))
+            .collect()
+    }
+}
+
+// Each standard lists its spellings to try, the final name first and the draft name second.
+static C_STANDARDS: &[(&str, &[&str])] = &[
+    ("c89", &["c89"]),
+    ("c99", &["c99"]),
+    ("c11", &["c11"]),
+    ("c17", &["c17"]),
+    ("c23", &["c23", "c2x"]),
+];
+static CPP_STANDARDS: &[(&str, &[&str])] = &[
+    ("c++11", &["c++11"]),
+    ("c++14", &["c++14"]),
+    ("c++17", &["c++17"]),
+    ("c++20", &["c++20", "c++2a"]),
+    ("c++23", &["c++23", "c++2b"]),
+];
+static CPP_HEADERS: &[&str] = &["filesystem", "span", "ranges", "format", "bits/stdc++.h"];
+
+static SANITIZERS: &[(&str, &str)] = &[
+    ("asan", "address"),
+    ("ubsan", "undefined"),
+    ("tsan", "thread"),
+];
+
+const C_MAIN: &str = "int main(void) { return 0; }\n";
+const CPP_MAIN: &str = "int main() { return 0; }\n";
+const C_THREADS: &str = r#"#include <pthread.h>
+static void *work(void *arg) { return arg; }
+int main(void) {
+    pthread_t t;
+    if (pthread_create(&t, 0, work, 0) != 0) return 1;
+    return pthread_join(t, 0);
+}
+"#;
+const CPP_THREADS: &str = r#"#include <thread>
+int main() {
+    std::thread t([] {});
+    t.join();
+    return 0;
+}
+"#;
+const OPENMP: &str = r#"#include <omp.h>
+int main(void) { return omp_get_max_threads() > 0 ? 0 : 1; }
+"#;
+
+pub fn std_flag(family: Family, std: &str) -> Option<String> {
+    match family {
+        Family::Msvc => match std {
+            "c11" | "c17" | "c++14" | "c++17" | "c++20" => Some(format!("/std:{}", std)),
+            _ => None,
+        },
+        Family::Tcc | Family::Pcc if std.starts_with("c++") => None,
+        _ => Some(format!("-std={}", std)),
+    }
+}
+
+fn sanitizer_flag(family: Family, sanitizer: &str) -> Option<String> {
+    match family {
+        Family::Gcc | Family::Clang | Family::Zig | Family::Intel => {
+            Some(format!("-fsanitize={}", sanitizer))
+        }
+        Family::Msvc if sanitizer == "address" => Some("/fsanitize=address".to_string()),
+        _ => None,
+    }
+}
+
+pub fn threads_flag(family: Family) -> Option<&'static str> {
+    match family {
+        Family::Msvc => Some(""),
+        Family::Gcc | Family::Clang | Family::Zig | Family::Intel | Family::Tcc | Family::Pcc => {
+            Some("-pthread")
+        }
+        _ => None,
+    }
+}
+
+pub fn openmp_flag(family: Family) -> Option<&'static str> {
+    match family {
+        Family::Gcc | Family::Clang => Some("-fopenmp"),
+        Family::Intel => Some("-qopenmp"),
+        Family::Msvc => Some("/openmp"),
+        _ => None,
+    }
+}
+
+pub fn static_flag(family: Family) -> Option<&'static str> {
+    match family {
+        Family::Msvc => Some("/MT"),
+        Family::Gcc | Family::Clang | Family::Zig | Family::Intel | Family::Tcc => Some("-static"),
+        _ => None,
+    }
+}
+
+struct Prober<'a> {
+    compiler: &'a str,
+    dir: PathBuf,
+    ext: &'static str,
+    count: usize,
+}
+
+impl Prober<'_> {
+    /// Builds `code` and returns the executable, or `None` when it does not build.
+    fn build(&mut self, code: &str, extra: &str) -> Option<PathBuf> {
+        self.count += 1;
+        let src = self.dir.join(format!("probe{}.{}", self.count, self.ext));
+        let exe = src.with_extension(if cfg!(windows) { "exe" } else { "out" });
+        if fs::write(&src, code).is_err() {
+            return None;
+        }
+        compile_quiet(
+            self.compiler,
+            &exe.to_string_lossy(),
+            &src.to_string_lossy(),
+            extra,
+        )
+        .then_some(exe)
+    }
+
+    fn try_build(&mut self, code: &str, extra: &str) -> bool {
+        self.build(code, extra).is_some()
+    }
+
+    /// Builds `code` and runs it: runtimes (sanitizers, OpenMP, static libc)
+    /// can link fine and still be missing or broken when the program starts.
+    fn try_run(&mut self, code: &str, extra: &str) -> bool {
+        self.build(code, extra).is_some_and(|exe| {
+            Command::new(exe)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success())
+        })
+    }
+}
+
+/// Builds small test programs with `compiler` to find out which language
+/// standards, sanitizers, threading/OpenMP support and headers it provides.
+/// Programs needing a runtime are also run, so a broken runtime counts as missing.
+fn probe_capabilities(compiler: &str, lang: Lang) -> Capabilities {
+    let family = family_of(compiler);
+
+    let dir = std::env::temp_dir().join(format!(
+        "crun-probe-{}-{}-{}",
+        std::process::id(),
+        compiler.replace(['/', '\\', ':', '+'], "_"),
+        lang.as_str()
+    ));
+    if fs::create_dir_all(&dir).is_err() {
+        return Capabilities::default();
+    }
+
+    let (ext, main_code, standards, threads_code) = match lang {
+        Lang::C => ("c", C_MAIN, C_STANDARDS, C_THREADS),
+        Lang::Cpp => ("cpp", CPP_MAIN, CPP_STANDARDS, CPP_THREADS),
+        _ => return Capabilities::default(),
+    };
+    let mut prober = Prober {
+        compiler,
+        dir: dir.clone(),
+        ext,
+        count: 0,
+    };
+    let mut features = Vec::new();
+
+    let mut newest_std = String::new();
+    for (name, spellings) in standards {
+        for spelling in *spellings {
+            let Some(flag) = std_flag(family, spelling) else {
+                continue;
+            };
+            if prober.try_build(main_code, &flag) {
+                features.push(format!("std={}", name));
+                newest_std = flag;
+                break;
+            }
+        }
+    }
+
+    for (name, sanitizer) in SANITIZERS {
+        if let Some(flag) = sanitizer_flag(family, sanitizer)
+            && prober.try_run(main_code, &flag)
+        {
+            features.push(name.to_string());
+        }
+    }
+
+    if let Some(flag) = threads_flag(family)
+        && prober.try_run(threads_code, flag)
+    {
+        features.push("threads".to_string());
+    }
+    if let Some(flag) = openmp_flag(family)
+        && prober.try_run(OPENMP, flag)
+    {
+        features.push("openmp".to_string());
+    }
+    if let Some(flag) = static_flag(family)
+        && prober.try_run(main_code, flag)
+    {
+        features.push("static".to_string());
+    }
+
+    if lang == Lang::Cpp {
+        for header in CPP_HEADERS {
+            let code = format!("#include <{}>\n{}", header, CPP_MAIN);
+            if prober.try_build(&code, &newest_std) {
+                features.push(format!("header=<{}>", header));
+            }
+        }
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+    Capabilities { features }
+}
+
+fn cache_kind(lang: Lang) -> String {
+    format!("caps-{}", lang.as_str())
+}
+
+/// Capabilities of `compiler`, probed on first use and cached per binary afterwards.
+pub fn capabilities(compiler: &str, lang: Lang) -> Capabilities {
+    let value = probe_cache::cached_or_probe(compiler, &cache_kind(lang), || {
+        probe_capabilities(compiler, lang).features.join(" ")
+    });
+    Capabilities {
+        features: value.split_whitespace().map(String::from).collect(),
+    }
+}
+
+/// Capabilities probed again during this run, keyed by compiler and language.
+static RECHECKED: LazyLock<Mutex<HashMap<String, Capabilities>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Probes `compiler` again and updates the cache, at most once per run. The
+/// cache only notices a changed compiler binary, so a feature recorded as
+/// missing is rechecked before it is reported: the runtime may have been
+/// installed since.
+pub fn recheck(compiler: &str, lang: Lang) -> Capabilities {
+    // held while probing so parallel callers wait for one probe instead of racing it
+    let mut rechecked = RECHECKED.lock().unwrap();
+    rechecked
+        .entry(format!("{}\t{}", compiler, lang.as_str()))
+        .or_insert_with(|| {
+            let caps = probe_capabilities(compiler, lang);
+            probe_cache::record(compiler, &cache_kind(lang), &caps.features.join(" "));
+            caps
+        })
+        .clone()
+}
+
+/// Capabilities recorded by an earlier probe, without probing on a miss.
+pub fn cached_capabilities(compiler: &str, lang: Lang) -> Option<Capabilities> {
+    let value = probe_cache::lookup(compiler, &cache_kind(lang))?;
+    Some(Capabilities {
+        features: value.split_whitespace().map(String::from).collect(),
+    })
+}
@@ -1,9 +1,9 @@
 use std::env;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 #[cfg(unix)]
-fn is_executable(path: &Path) -> bool {
-    use std::fs;
+pub fn is_executable(path: &Path) -> bool {
     use std::os::unix::fs::PermissionsExt;
     fs::metadata(path)
         .map(|m| m.is_file() && (m.permissions().mode() & 0o111 != 0))
@@ -11,7 +11,7 @@ fn is_executable(path: &Path) -> bool {
 }
 
 #[cfg(windows)]
-fn is_executable(path: &Path) -> bool {
+pub fn is_executable(path: &Path) -> bool {
     matches!(
         path.extension().and_then(|e| e.to_str()).map(|s| s.to_ascii_lowercase()),
         Some(ext) if ["exe", "bat", "cmd", "com"].contains(&ext.as_str())
@@ -43,6 +43,14 @@ pub fn command_exists(cmd: &str) -> bool {
 }
 
 pub fn find_command(cmd: &str) -> String {
+    let path = Path::new(cmd);
+    if path.components().count() > 1 {
+        return if is_executable(path) {
+            cmd.to_string()
+        } else {
+            String::new()
+        };
+    }
     if let Some(paths) = env::var_os("PATH") {
         for dir in env::split_paths(&paths) {
             let candidate = dir.join(cmd);
@@ -61,3 +69,167 @@ pub fn find_command(cmd: &str) -> String {
     }
     String::new()
 }
+
+/// Every PATH match for `cmd`, in lookup order. More than one entry means
+/// the first one shadows the rest.
+pub fn find_all_commands(cmd: &str) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = Vec::new();
+    if let Some(paths) = env::var_os("PATH") {
+        for dir in env::split_paths(&paths) {
+            #[cfg(windows)]
+            let candidates: Vec<PathBuf> = std::iter::once(dir.join(cmd))
+                .chain(["exe", "bat", "cmd", "com"].map(|ext| dir.join(cmd).with_extension(ext)))
+                .collect();
+            #[cfg(not(windows))]
+            let candidates = vec![dir.join(cmd)];
+            for candidate in candidates {
+                if is_executable(&candidate) {
+                    let resolved = fs::canonicalize(&candidate).unwrap_or(candidate.clone());
+                    // the same binary reached twice (e.g. /bin -> /usr/bin) is not shadowing
+                    if !found
+                        .iter()
+                        .any(|f| fs::canonicalize(f).unwrap_or(f.clone()) == resolved)
+                    {
+                        found.push(candidate);
+                    }
+                }
+            }
+        }
+    }
+    found
+}
+
+/// PATH entries named `cmd` that are symlinks pointing at nothing,
+/// paired with their dangling target.
+pub fn find_broken_links(cmd: &str) -> Vec<(PathBuf, PathBuf)> {
+    let mut broken = Vec::new();
+    if let Some(paths) = env::var_os("PATH") {
+        for dir in env::split_paths(&paths) {
+            let candidate = dir.join(cmd);
+            let is_link = fs::symlink_metadata(&candidate)
+                .map(|m| m.file_type().is_symlink())
+                .unwrap_or(false);
+            if is_link && fs::metadata(&candidate).is_err() {
+                let target = fs::read_link(&candidate).unwrap_or_default();
+                broken.push((candidate, target));
+            }
+        }
+    }
+    broken
+}
+
+/// Directories where toolchains are commonly installed without being put on PATH.
+pub fn known_prefix_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+
+    if cfg!(windows) {
+        for var in ["ProgramFiles", "ProgramFiles(x86)"] {
+            if let Some(pf) = env::var_os(var) {
+                let pf = PathBuf::from(pf);
+                dirs.push(pf.join("LLVM").join("bin"));
+                dirs.push(pf.join("mingw64").join("bin"));
+            }
+        }
+        dirs.push(PathBuf::from(r"C:\msys64\mingw64\bin"));
+        dirs.push(PathBuf::from(r"C:\msys64\ucrt64\bin"));
+        dirs.push(PathBuf::from(r"C:\msys64\clang64\bin"));
+        dirs.push(PathBuf::from(r"C:\TDM-GCC-64\bin"));
+    } else {
+        // versioned LLVM installs (Debian/Ubuntu apt.llvm.org) and /opt toolchains
+        for (parent, prefix) in [("/usr/lib", "llvm"), ("/opt", "")] {
+            if let Ok(entries) = fs::read_dir(parent) {
+                let mut found: Vec<PathBuf> = entries
+                    .flatten()
+                    .filter(|e| e.file_name().to_string_lossy().starts_with(prefix))
+                    .map(|e| e.path().join("bin"))
+                    .collect();
+                found.sort();
+                dirs.extend(found);
+            }
+        }
+        for dir in [
+            "/usr/local/bin",
+            "/usr/local/opt/llvm/bin",
+            "/opt/homebrew/bin",
+            "/opt/homebrew/opt/llvm/bin",
+            "/opt/intel/oneapi/compiler/latest/bin",
+            "/nix/var/nix/profiles/default/bin",
+            "/run/current-system/sw/bin",
+        ] {
+            dirs.push(PathBuf::from(dir));
+        }
+        if let Some(home) = env::var_os("HOME") {
+            let home = PathBuf::from(home);
+            dirs.push(home.join(".local").join("bin"));
+            dirs.push(home.join(".nix-profile").join("bin"));
+        }
+    }
+
+    let on_path: Vec<PathBuf> = env::var_os("PATH")
+        .map(|p| env::split_paths(&p).collect())
+        .unwrap_or_default();
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if dir.is_dir() && !on_path.contains(&dir) && !unique.contains(&dir) {
+            unique.push(dir);
+        }
+    }
+    unique
+}
+
+/// Installations of `cmd` in [`known_prefix_dirs`] that PATH does not reach.
+pub fn find_outside_path(cmd: &str) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for dir in known_prefix_dirs() {
+        let candidate = dir.join(cmd);
+        if is_executable(&candidate) {
+            found.push(candidate);
+            continue;
+        }
+        #[cfg(windows)]
+        if is_executable(&candidate.with_extension("exe")) {
+            found.push(candidate.with_extension("exe"));
+        }
+    }
+    found
+}
+
+/// Executables in PATH and [`known_prefix_dirs`] whose file name passes `keep`,
+/// paired with whether they are reachable through PATH. A binary reached under
+/// several names (`gcc` -> `gcc-12`) is listed once, under the first name found.
+pub fn find_commands_matching(keep: impl Fn(&str) -> bool) -> Vec<(PathBuf, bool)> {
+    let on_path: Vec<PathBuf> = env::var_os("PATH")
+        .map(|p| env::split_paths(&p).collect())
+        .unwrap_or_default();
+    let dirs = on_path
+        .iter()
+        .map(|d| (d.clone(), true))
+        .chain(known_prefix_dirs().into_iter().map(|d| (d, false)));
+
+    let mut seen: Vec<PathBuf> = Vec::new();
+    let mut found = Vec::new();
+    for (dir, reachable) in dirs {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut names: Vec<String> = entries
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|name| keep(name))
+            .collect();
+        // "gcc" sorts before "gcc-12", so the unversioned name wins for a shared binary
+        names.sort();
+        for name in names {
+            let candidate = dir.join(&name);
+            if !is_executable(&candidate) {
+                continue;
+            }
+            let resolved = fs::canonicalize(&candidate).unwrap_or(candidate.clone());
+            if !seen.contains(&resolved) {
+                seen.push(resolved);
+                found.push((candidate, reachable));
+            }
+        }
+    }
+    found
+}
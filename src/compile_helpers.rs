@@ -1,132 +1,421 @@
+use crate::backends;
+use crate::build_env::{BuildOption, OptionKind, Setting};
+use crate::compiler_registry::{self, ExtraPosition, Family, Installation, Lang};
+use crate::compiler_version::{self, CompilerSpec};
+use crate::probe_cache::parallel_map;
 use crate::{LOG, command_exists};
-use std::process;
+use std::cmp::Reverse;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
 
-pub fn detect_compiler(preferred: &str, src_file: &str) -> String {
-    use std::path::Path;
-
-    static C_COMPILERS: &[&str] = &["gcc", "clang", "zig", "cl", "icc", "tcc", "pcc"];
-    static CPP_COMPILERS: &[&str] = &["g++", "clang++", "cl", "icpc"];
+/// The language a source file is compiled as, going by its extension.
+/// Files no backend claims (headers) are treated as C.
+pub fn source_lang(src_file: &str) -> Lang {
+    backends::for_source(src_file).map_or(Lang::C, |b| b.lang)
+}
 
+pub fn detect_compiler(preferred: &str, src_file: &str) -> String {
     let ext = Path::new(src_file)
         .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("")
-        .to_lowercase();
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let backend = backends::for_source(src_file).unwrap_or(&backends::BACKENDS[0]);
+    let is_gas = crate::assembly::is_gas_source(src_file);
+    LOG.lock().unwrap().println(
+        &format_args!(
+            "Detected {} source file based on extension '.{}'",
+            backend.name, ext
+        ),
+        None,
+    );
 
-    let is_cpp = matches!(ext.as_str(), "cpp" | "cc" | "cxx");
+    // 1. Preferred compiler override, optionally with version requirements
+    //    (e.g. "gcc>=12" or "gcc>=12,clang>=15" to try them in order)
+    if !preferred.is_empty() {
+        return select_preferred(preferred);
+    }
 
-    if is_cpp {
-        unsafe {
-            LOG.lock().unwrap().println(
-                &format_args!("Detected C++ source file based on extension '.{}'", ext),
-                None,
-            );
+    // 2. Try matching compilers, skipping installs that are too old
+    let lang = backend.lang;
+    for c in compiler_registry::candidate_names(lang) {
+        // only the gcc-style drivers assemble .s/.S files themselves
+        if is_gas
+            && !matches!(
+                compiler_registry::family_of(c),
+                Family::Gcc | Family::Clang | Family::Zig | Family::Intel
+            )
+        {
+            continue;
         }
-    } else {
-        unsafe {
-            LOG.lock().unwrap().println(
-                &format_args!("Detected C source file based on extension '.{}'", ext),
-                None,
-            );
+        if command_exists::command_exists(compiler_registry::command_for(c))
+            && compiler_registry::has_front_end(c, lang)
+            && is_recent_enough(c)
+        {
+            return c.to_string();
         }
     }
 
-    // 1. Preferred compiler override
-    if !preferred.is_empty() {
-        if command_exists::command_exists(preferred) {
-            return preferred.to_string();
-        } else {
-            unsafe {
+    // 3. Fallback: C file but no C compiler → try C++ compilers
+    if lang == Lang::C {
+        for c in compiler_registry::candidate_names(Lang::Cpp) {
+            if command_exists::command_exists(compiler_registry::command_for(c))
+                && is_recent_enough(c)
+            {
                 LOG.lock().unwrap().println(
-                    &format_args!("Preferred compiler '{}' not found", preferred),
+                    &format_args!(
+                        "⚠️ No valid C compiler found. Using '{}' (a C++ compiler) for C source",
+                        c
+                    ),
                     None,
                 );
+                println!("Detected compiler: {}", c);
+                return c.to_string();
             }
-            std::process::exit(1);
         }
     }
 
-    // 2. Try matching compilers
-    let candidates = if is_cpp { CPP_COMPILERS } else { C_COMPILERS };
-    for &c in candidates {
-        if command_exists::command_exists(c) {
-            return c.to_string();
+    // 4. No compiler found
+    String::new()
+}
+
+fn is_recent_enough(compiler: &str) -> bool {
+    let Some(min) = compiler_version::minimum_supported(compiler) else {
+        return true;
+    };
+    match compiler_version::cached_compiler_version(compiler) {
+        Some(version) if !min.matches(&version) => {
+            LOG.lock().unwrap().println(
+                &format_args!(
+                    "Skipping '{}': version {} is older than the minimum supported {}",
+                    compiler, version, min
+                ),
+                None,
+            );
+            false
         }
+        _ => true,
     }
+}
 
-    // 3. Fallback: C file but no C compiler → try C++ compilers
-    if !is_cpp {
-        for &c in CPP_COMPILERS {
-            if command_exists::command_exists(c) {
-                unsafe {
+fn select_preferred(preferred: &str) -> String {
+    let specs: Vec<CompilerSpec> = match preferred
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(compiler_version::parse_compiler_spec)
+        .collect()
+    {
+        Ok(specs) => specs,
+        Err(e) => {
+            LOG.lock().unwrap().println(
+                &format_args!("Invalid compiler '{}': {}", preferred, e),
+                None,
+            );
+            std::process::exit(1);
+        }
+    };
+
+    for spec in &specs {
+        let installed = command_exists::command_exists(compiler_registry::command_for(&spec.name));
+        let Some(req) = &spec.req else {
+            if installed {
+                return spec.name.clone();
+            }
+            LOG.lock().unwrap().println(
+                &format_args!("Preferred compiler '{}' not found", spec.name),
+                None,
+            );
+            continue;
+        };
+
+        // the PATH default first, then other installs of the same compiler, newest first
+        let mut choices: Vec<String> = Vec::new();
+        if installed {
+            choices.push(spec.name.clone());
+        }
+        choices.extend(other_installations(&spec.name));
+        if choices.is_empty() {
+            LOG.lock().unwrap().println(
+                &format_args!("Preferred compiler '{}' not found", spec.name),
+                None,
+            );
+            continue;
+        }
+
+        for choice in choices {
+            match compiler_version::cached_compiler_version(&choice) {
+                Some(version) if req.matches(&version) => return choice,
+                Some(version) => {
                     LOG.lock().unwrap().println(
                         &format_args!(
-                            "⚠️ No valid C compiler found. Using '{}' (a C++ compiler) for C source",
-                            c
+                            "Preferred compiler '{}' is {}, which does not satisfy {}",
+                            choice, version, spec
+                        ),
+                        None,
+                    );
+                }
+                None => {
+                    LOG.lock().unwrap().println(
+                        &format_args!(
+                            "Could not determine the version of '{}' to check {}",
+                            choice, spec
                         ),
                         None,
                     );
                 }
-                println!("Detected compiler: {}", c);
-                return c.to_string();
             }
         }
     }
+    crate::exit_with(&format!(
+        "No installed compiler satisfies '{}'; run 'crun -list-all' to see what is installed",
+        preferred
+    ));
+}
 
-    // 4. No compiler found
-    String::new()
+/// Installations of the registered compiler `name` other than the one PATH
+/// resolves it to (`gcc-12`, `/usr/lib/llvm-17/bin/clang`), newest first.
+/// Explicit paths and unregistered names have none.
+fn other_installations(name: &str) -> Vec<String> {
+    if compiler_registry::lookup(name).is_none() {
+        return Vec::new();
+    }
+    let default = fs::canonicalize(compiler_registry::resolve_path(name)).ok();
+    let installs: Vec<Installation> = compiler_registry::discover_installations()
+        .into_iter()
+        .filter(|i| i.core == name && fs::canonicalize(&i.path).ok() != default)
+        .collect();
+
+    let mut versioned = parallel_map(installs, |i| {
+        let selector = i.selector();
+        let version = compiler_version::cached_compiler_version(&selector)
+            .map(|v| (v.major, v.minor, v.patch));
+        (selector, version)
+    });
+    versioned.sort_by_key(|(_, version)| Reverse(*version));
+    versioned
+        .into_iter()
+        .map(|(selector, _)| selector)
+        .collect()
 }
 
-pub fn compile(compiler: &str, exe: &str, source: &str, extra: &str) -> bool {
-    let mut args: Vec<String> = match compiler {
-        // GCC family
-        "gcc" | "g++" => vec!["-o".into(), exe.into(), source.into()],
-
-        // Clang family
-        "clang" | "clang++" => vec![
-            "-o".into(),
-            exe.into(),
-            source.into(),
-            "-Wno-deprecated-declarations".into(),
-            "-D_CRT_SECURE_NO_WARNINGS".into(),
-        ],
-
-        // MSVC
-        "cl" => vec![format!("/Fe:{}", exe), source.into()],
-
-        // Zig
-        "zig" => vec!["cc".into(), "-o".into(), exe.into(), source.into()],
-
-        // Intel
-        "icc" | "icpc" => vec!["-o".into(), exe.into(), source.into()],
-
-        // Lightweight / niche
-        "tcc" | "pcc" | "lcc" => vec!["-o".into(), exe.into(), source.into()],
-        "wcl" => vec![format!("-fe={}", exe), source.into()],
-        "bcc32" => vec![format!("-e{}", exe), source.into()],
-        "dmc" => vec![format!("-o{}", exe), source.into()],
-        "sdcc" => vec!["-o".into(), exe.into(), source.into()],
-
-        // Fallback
-        _ => vec!["-o".into(), exe.into(), source.into()],
+/// `option` in the syntax of `family`, e.g. `-Iinclude` for gcc and `/Iinclude` for cl.
+fn option_flag(family: Family, option: &BuildOption) -> Result<String, String> {
+    let value = &option.value;
+    // a path to a library file is linked as given by every family
+    let is_file = value.contains(['/', '\\'])
+        || Path::new(value).extension().is_some_and(|e| {
+            ["a", "so", "lib", "dylib", "o", "obj"].contains(&&*e.to_string_lossy())
+        });
+    Ok(match (option.kind, family) {
+        (_, Family::Rustc | Family::Go) | (OptionKind::Define, Family::Dmd) => {
+            return Err(format!(
+                "{} is not supported for the {} family; pass the compiler's own flags with -e",
+                option.kind.flag(),
+                family.as_str()
+            ));
+        }
+        // dmd and ldc2 hand -L arguments to the linker
+        (OptionKind::LibDir, Family::Dmd) => format!("-L-L{}", value),
+        (OptionKind::Link, Family::Dmd) if !is_file => format!("-L-l{}", value),
+        (OptionKind::Include, Family::Msvc) => format!("/I{}", value),
+        (OptionKind::Include, Family::Watcom) => format!("-i={}", value),
+        (OptionKind::Include, _) => format!("-I{}", value),
+        (OptionKind::Define, Family::Msvc) => format!("/D{}", value),
+        (OptionKind::Define, Family::Watcom) => format!("-d{}", value),
+        (OptionKind::Define, _) => format!("-D{}", value),
+        (OptionKind::LibDir, Family::Msvc) => format!("/LIBPATH:{}", value),
+        (OptionKind::LibDir, Family::Watcom | Family::DigitalMars) => {
+            return Err(format!(
+                "--lib-dir is not supported for the {} family; pass the library's full path to --link",
+                family.as_str()
+            ));
+        }
+        (OptionKind::LibDir, _) => format!("-L{}", value),
+        (OptionKind::Link, _) if is_file || value.starts_with('-') => value.clone(),
+        (
+            OptionKind::Link,
+            Family::Msvc | Family::Watcom | Family::Borland | Family::DigitalMars,
+        ) => format!("{}.lib", value),
+        (OptionKind::Link, _) => format!("-l{}", value),
+    })
+}
+
+/// Translates `-I`, `-D`, `--lib-dir` and `--link` options into `compiler`'s
+/// syntax. Returns the compile flags and the link flags (which [`compile_args`]
+/// places after the source), grouped by where they came from.
+pub fn option_flags(
+    compiler: &str,
+    options: &[BuildOption],
+) -> Result<(Vec<Setting>, Vec<Setting>), String> {
+    let family = compiler_registry::family_of(compiler);
+    let (mut compile, mut link): (Vec<Setting>, Vec<Setting>) = (Vec::new(), Vec::new());
+    for option in options {
+        let flag = option_flag(family, option)?;
+        let group = if option.kind.is_link() {
+            &mut link
+        } else {
+            &mut compile
+        };
+        match group.last_mut() {
+            Some(last) if last.source == option.source => {
+                last.value.push(' ');
+                last.value.push_str(&flag);
+            }
+            _ => group.push(Setting {
+                value: flag,
+                source: option.source.clone(),
+            }),
+        }
+    }
+    Ok((compile, link))
+}
+
+/// Builds the driver's argument vector. `extra` goes where the compiler family
+/// expects user flags; `link` goes after the source, behind `/link` for cl.
+pub fn compile_args(
+    compiler: &str,
+    exe: &str,
+    source: &str,
+    extra: &str,
+    link: &str,
+) -> Vec<String> {
+    let extra_args = extra.split_whitespace().map(String::from).collect();
+    driver_args(compiler, exe, source, extra_args, link)
+}
+
+/// [`compile_args`] with the user flags already split into arguments.
+fn driver_args(
+    compiler: &str,
+    exe: &str,
+    source: &str,
+    extra_args: Vec<String>,
+    link: &str,
+) -> Vec<String> {
+    let def = compiler_registry::definition_for(compiler);
+    let mut args = def.prefix_args.clone();
+    if def.extra_position == ExtraPosition::BeforeOutput {
+        args.extend(extra_args.iter().cloned());
+    }
+    args.extend(def.output_args(exe));
+    args.extend(def.default_args.iter().cloned());
+    if def.extra_position == ExtraPosition::BeforeSource {
+        args.extend(extra_args.iter().cloned());
+    }
+    args.push(source.to_string());
+    if def.extra_position == ExtraPosition::Append {
+        args.extend(extra_args);
+    }
+    if !link.trim().is_empty() {
+        if def.family == Family::Msvc {
+            args.push("/link".to_string());
+        }
+        args.extend(link.split_whitespace().map(String::from));
+    }
+    args
+}
+
+/// Copies `from` into `to` as it arrives, keeping what was copied.
+fn tee(mut from: impl Read, mut to: impl Write) -> Vec<u8> {
+    let mut kept = Vec::new();
+    let mut buf = [0u8; 4096];
+    while let Ok(n) = from.read(&mut buf) {
+        if n == 0 {
+            break;
+        }
+        let _ = to.write_all(&buf[..n]);
+        let _ = to.flush();
+        kept.extend_from_slice(&buf[..n]);
+    }
+    kept
+}
+
+/// Compiles `source` into `exe`, launched through `wrapper` (ccache, sccache) when given.
+/// `verbatim` arguments follow `extra` without being split, so paths in them may
+/// contain spaces. The compiler's output is shown as it arrives and also returned,
+/// so a failure can be explained without compiling again.
+pub fn compile(
+    compiler: &str,
+    exe: &str,
+    source: &str,
+    extra: &str,
+    verbatim: &[String],
+    link: &str,
+    wrapper: Option<&str>,
+) -> (bool, String) {
+    let extra_args = extra
+        .split_whitespace()
+        .map(String::from)
+        .chain(verbatim.iter().cloned())
+        .collect();
+    let mut args = driver_args(compiler, exe, source, extra_args, link);
+    // the output goes through a pipe, so ask for the colours a terminal would get
+    if io::stderr().is_terminal()
+        && matches!(
+            compiler_registry::family_of(compiler),
+            Family::Gcc | Family::Clang
+        )
+    {
+        args.insert(0, "-fdiagnostics-color=always".to_string());
+    }
+    let program = match wrapper {
+        Some(wrapper) => {
+            args.insert(0, compiler_registry::command_for(compiler).to_string());
+            wrapper
+        }
+        None => compiler_registry::command_for(compiler),
+    };
+    let mut child = match Command::new(program)
+        .args(&args)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => return (false, e.to_string()),
     };
+    // cl reports errors on stdout, the others on stderr; both are read at once so neither pipe fills up
+    let stdout = child
+        .stdout
+        .take()
+        .map(|out| std::thread::spawn(move || tee(out, io::stdout())));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|err| std::thread::spawn(move || tee(err, io::stderr())));
+    let success = child.wait().is_ok_and(|s| s.success());
+    let mut text = String::new();
+    for reader in [stdout, stderr].into_iter().flatten() {
+        text.push_str(&String::from_utf8_lossy(&reader.join().unwrap_or_default()));
+    }
+    (success, text)
+}
 
-    // Inject extra flags
-    if !extra.is_empty() {
-        let extra_args: Vec<String> = extra.split_whitespace().map(String::from).collect();
-        match compiler {
-            "cl" => {
-                args.splice(1..1, extra_args);
-            } // insert after /Fe
-            "bcc32" | "dmc" | "wcl" => {
-                args.extend(extra_args);
-            } // append
-            _ => {
-                args.splice(args.len() - 1..args.len() - 1, extra_args);
-            } // before source
+/// Same as [`compile`], but returns the compiler's combined output instead of printing it.
+pub fn compile_captured(compiler: &str, exe: &str, source: &str, extra: &str) -> (bool, String) {
+    match Command::new(compiler_registry::command_for(compiler))
+        .args(compile_args(compiler, exe, source, extra, ""))
+        .stdin(Stdio::null())
+        .output()
+    {
+        Ok(output) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            (output.status.success(), text)
         }
+        Err(e) => (false, e.to_string()),
     }
+}
 
-    let arg_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-    crate::run_command(compiler, &arg_refs)
+/// Same as [`compile`], but discards the compiler's output. Used for probing.
+pub fn compile_quiet(compiler: &str, exe: &str, source: &str, extra: &str) -> bool {
+    Command::new(compiler_registry::command_for(compiler))
+        .args(compile_args(compiler, exe, source, extra, ""))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
 }
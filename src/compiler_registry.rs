@@ -0,0 +1,537 @@
+use crate::command_exists::{find_command, find_commands_matching};
+use crate::compiler_version;
+use crate::config;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::LazyLock;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lang {
+    C,
+    Cpp,
+    ObjC,
+    Fortran,
+    Zig,
+    Rust,
+    Go,
+    D,
+}
+
+impl Lang {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Lang::C => "c",
+            Lang::Cpp => "cpp",
+            Lang::ObjC => "objc",
+            Lang::Fortran => "fortran",
+            Lang::Zig => "zig",
+            Lang::Rust => "rust",
+            Lang::Go => "go",
+            Lang::D => "d",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Lang> {
+        match text.trim().to_ascii_lowercase().as_str() {
+            "c" => Some(Lang::C),
+            "cpp" | "c++" | "cxx" => Some(Lang::Cpp),
+            "objc" | "objective-c" => Some(Lang::ObjC),
+            "fortran" => Some(Lang::Fortran),
+            "zig" => Some(Lang::Zig),
+            "rust" => Some(Lang::Rust),
+            "go" => Some(Lang::Go),
+            "d" => Some(Lang::D),
+            _ => None,
+        }
+    }
+}
+
+/// Compiler families share command-line conventions; anything crun knows how
+/// to translate (flags, probes, hints) is keyed on the family, not the name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Family {
+    Gcc,
+    Clang,
+    Zig,
+    Msvc,
+    Intel,
+    Tcc,
+    Pcc,
+    Watcom,
+    Borland,
+    DigitalMars,
+    Sdcc,
+    Rustc,
+    Go,
+    Dmd,
+    Generic,
+}
+
+impl Family {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Family::Gcc => "gcc",
+            Family::Clang => "clang",
+            Family::Zig => "zig",
+            Family::Msvc => "msvc",
+            Family::Intel => "intel",
+            Family::Tcc => "tcc",
+            Family::Pcc => "pcc",
+            Family::Watcom => "watcom",
+            Family::Borland => "borland",
+            Family::DigitalMars => "dmc",
+            Family::Sdcc => "sdcc",
+            Family::Rustc => "rustc",
+            Family::Go => "go",
+            Family::Dmd => "dmd",
+            Family::Generic => "generic",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Family> {
+        let family = match text.trim().to_ascii_lowercase().as_str() {
+            "gcc" | "gnu" => Family::Gcc,
+            "clang" | "llvm" => Family::Clang,
+            "zig" => Family::Zig,
+            "msvc" | "cl" => Family::Msvc,
+            "intel" | "icc" => Family::Intel,
+            "tcc" => Family::Tcc,
+            "pcc" => Family::Pcc,
+            "watcom" | "wcl" => Family::Watcom,
+            "borland" | "bcc32" => Family::Borland,
+            "dmc" | "digitalmars" => Family::DigitalMars,
+            "sdcc" => Family::Sdcc,
+            "rustc" | "rust" => Family::Rustc,
+            "go" => Family::Go,
+            "dmd" | "ldc" | "ldc2" => Family::Dmd,
+            "generic" => Family::Generic,
+            _ => return None,
+        };
+        Some(family)
+    }
+}
+
+/// Where `-e` flags go relative to the rest of the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExtraPosition {
+    BeforeOutput,
+    BeforeSource,
+    Append,
+}
+
+impl ExtraPosition {
+    fn parse(text: &str) -> Option<ExtraPosition> {
+        match text.trim() {
+            "before-output" => Some(ExtraPosition::BeforeOutput),
+            "before-source" => Some(ExtraPosition::BeforeSource),
+            "append" => Some(ExtraPosition::Append),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CompilerDef {
+    /// Name used with `-c` and in listings.
+    pub name: String,
+    /// Executable to run; defaults to `name`, may be a path or wrapper script.
+    pub command: String,
+    pub family: Family,
+    pub languages: Vec<Lang>,
+    /// Output flag template, `{out}` is replaced by the executable path.
+    pub output: String,
+    pub extra_position: ExtraPosition,
+    /// Arguments placed before everything else (`zig cc`).
+    pub prefix_args: Vec<String>,
+    /// Arguments always passed after the output flag.
+    pub default_args: Vec<String>,
+    pub version_args: Vec<String>,
+    /// Lower runs first during auto-detection.
+    pub priority: i32,
+    /// Whether auto-detection may pick this compiler; otherwise only `-c` selects it.
+    pub detect: bool,
+}
+
+impl CompilerDef {
+    pub fn supports(&self, lang: Lang) -> bool {
+        self.languages.contains(&lang)
+    }
+
+    pub fn output_args(&self, exe: &str) -> Vec<String> {
+        self.output
+            .split_whitespace()
+            .map(|part| part.replace("{out}", exe))
+            .collect()
+    }
+}
+
+fn split(text: &str) -> Vec<String> {
+    text.split_whitespace().map(String::from).collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn builtin(
+    name: &str,
+    family: Family,
+    languages: &[Lang],
+    output: &str,
+    extra_position: ExtraPosition,
+    prefix: &str,
+    defaults: &str,
+    version: &str,
+    priority: i32,
+    detect: bool,
+) -> CompilerDef {
+    CompilerDef {
+        name: name.to_string(),
+        command: name.to_string(),
+        family,
+        languages: languages.to_vec(),
+        output: output.to_string(),
+        extra_position,
+        prefix_args: split(prefix),
+        default_args: split(defaults),
+        version_args: split(version),
+        priority,
+        detect,
+    }
+}
+
+fn zig_build_exe() -> CompilerDef {
+    CompilerDef {
+        command: "zig".to_string(),
+        ..builtin(
+            "zig-build-exe",
+            Family::Zig,
+            &[Lang::Zig],
+            "-femit-bin={out}",
+            ExtraPosition::BeforeSource,
+            "build-exe",
+            "",
+            "version",
+            10,
+            true,
+        )
+    }
+}
+
+#[rustfmt::skip]
+fn builtins() -> Vec<CompilerDef> {
+    use ExtraPosition::*;
+    use Family::*;
+    use Lang::{C, Cpp, D, Fortran, ObjC, Rust};
+
+    let clang_defaults = "-Wno-deprecated-declarations -D_CRT_SECURE_NO_WARNINGS";
+    vec![
+        // name, family, languages, output, extra, prefix, defaults, version, priority, detect
+        builtin("gcc", Gcc, &[C, ObjC], "-o {out}", BeforeSource, "", "", "--version", 10, true),
+        builtin("g++", Gcc, &[Cpp], "-o {out}", BeforeSource, "", "", "--version", 10, true),
+        builtin("clang", Clang, &[C, ObjC], "-o {out}", BeforeSource, "", clang_defaults, "--version", 20, true),
+        builtin("clang++", Clang, &[Cpp], "-o {out}", BeforeSource, "", clang_defaults, "--version", 20, true),
+        builtin("zig", Zig, &[C], "-o {out}", BeforeSource, "cc", "", "version", 30, true),
+        // MSVC prints its banner when run without arguments
+        builtin("cl", Msvc, &[C, Cpp], "/Fe:{out}", BeforeSource, "", "", "", 40, true),
+        builtin("icc", Intel, &[C], "-o {out}", BeforeSource, "", "", "--version", 50, true),
+        builtin("icpc", Intel, &[Cpp], "-o {out}", BeforeSource, "", "", "--version", 50, true),
+        builtin("tcc", Tcc, &[C], "-o {out}", BeforeSource, "", "", "-v", 60, true),
+        builtin("pcc", Pcc, &[C], "-o {out}", BeforeSource, "", "", "-v", 70, true),
+        builtin("gfortran", Gcc, &[Fortran], "-o {out}", BeforeSource, "", "", "--version", 10, true),
+        builtin("flang", Clang, &[Fortran], "-o {out}", BeforeSource, "", "", "--version", 20, true),
+        // `zig cc` above builds C; this entry runs the same binary on Zig sources
+        zig_build_exe(),
+        builtin("rustc", Rustc, &[Rust], "-o {out}", BeforeSource, "", "", "--version", 10, true),
+        builtin("go", Go, &[Lang::Go], "-o {out}", BeforeSource, "build", "", "version", 10, true),
+        builtin("dmd", Dmd, &[D], "-of={out}", BeforeSource, "", "", "--version", 10, true),
+        builtin("ldc2", Dmd, &[D], "-of={out}", BeforeSource, "", "", "--version", 20, true),
+        builtin("gdc", Gcc, &[D], "-o {out}", BeforeSource, "", "", "--version", 30, true),
+        // niche compilers, only used when picked with -c
+        builtin("lcc", Generic, &[C], "-o {out}", BeforeSource, "", "", "-version", 80, false),
+        builtin("wcl", Watcom, &[C, Cpp], "-fe={out}", Append, "", "", "-h", 80, false),
+        builtin("bcc32", Borland, &[C, Cpp], "-e{out}", Append, "", "", "-v", 80, false),
+        builtin("dmc", DigitalMars, &[C, Cpp], "-o{out}", Append, "", "", "-v", 80, false),
+        builtin("sdcc", Sdcc, &[C], "-o {out}", BeforeSource, "", "", "--version", 80, false),
+    ]
+}
+
+/// Applies a `[compiler <name>]` config section on top of `def`. Unknown
+/// values are reported and leave the field unchanged.
+fn apply_section(def: &mut CompilerDef, section: &config::Section) {
+    for (key, value) in &section.entries {
+        let ok = match key.as_str() {
+            "command" => {
+                def.command = value.clone();
+                true
+            }
+            "family" => Family::parse(value).map(|f| def.family = f).is_some(),
+            "languages" => {
+                let langs: Option<Vec<Lang>> = value
+                    .split(',')
+                    .filter(|s| !s.trim().is_empty())
+                    .map(Lang::parse)
+                    .collect();
+                langs.map(|l| def.languages = l).is_some()
+            }
+            "output" => {
+                def.output = value.clone();
+                true
+            }
+            "extra" => ExtraPosition::parse(value)
+                .map(|p| def.extra_position = p)
+                .is_some(),
+            "prefix" => {
+                def.prefix_args = split(value);
+                true
+            }
+            "args" => {
+                def.default_args = split(value);
+                true
+            }
+            "version" => {
+                def.version_args = split(value);
+                true
+            }
+            "priority" => value.parse().map(|p| def.priority = p).is_ok(),
+            "detect" => value.parse().map(|d| def.detect = d).is_ok(),
+            _ => false,
+        };
+        if !ok {
+            println!(
+                "Ignoring `{} = {}` in [compiler {}]",
+                key, value, section.name
+            );
+        }
+    }
+}
+
+fn build_registry() -> Vec<CompilerDef> {
+    let mut defs = builtins();
+    for section in config::sections("compiler") {
+        if section.name.is_empty() {
+            continue;
+        }
+        match defs.iter_mut().find(|d| d.name == section.name) {
+            Some(def) => apply_section(def, section),
+            None => {
+                // new toolchains start out as gcc-compatible C compilers
+                let mut def = builtin(
+                    &section.name,
+                    Family::Gcc,
+                    &[Lang::C],
+                    "-o {out}",
+                    ExtraPosition::BeforeSource,
+                    "",
+                    "",
+                    "--version",
+                    100,
+                    true,
+                );
+                apply_section(&mut def, section);
+                defs.push(def);
+            }
+        }
+    }
+    // stable sort: equal priorities keep the built-in order
+    defs.sort_by_key(|d| d.priority);
+    defs
+}
+
+static REGISTRY: LazyLock<Vec<CompilerDef>> = LazyLock::new(build_registry);
+
+pub fn lookup(name: &str) -> Option<&'static CompilerDef> {
+    REGISTRY.iter().find(|d| d.name == name)
+}
+
+/// Compilers auto-detection may pick for `lang`, in priority order.
+pub fn candidates(lang: Lang) -> Vec<&'static CompilerDef> {
+    REGISTRY
+        .iter()
+        .filter(|d| d.detect && d.supports(lang))
+        .collect()
+}
+
+/// Names of the auto-detectable compilers for `lang`.
+pub fn candidate_names(lang: Lang) -> Vec<&'static str> {
+    candidates(lang).iter().map(|d| d.name.as_str()).collect()
+}
+
+/// The executable behind a compiler name; unregistered names run as-is.
+pub fn command_for(name: &str) -> &str {
+    lookup(name).map(|d| d.command.as_str()).unwrap_or(name)
+}
+
+/// Full path of the executable behind `name`, or an empty string when it is not installed.
+pub fn resolve_path(name: &str) -> String {
+    find_command(command_for(name))
+}
+
+/// Whether `name` can compile `lang` as installed. gcc's Objective-C front end
+/// (cc1obj) is packaged separately from the driver, so gcc only builds `.m`
+/// files when the driver can find it.
+pub fn has_front_end(name: &str, lang: Lang) -> bool {
+    if lang != Lang::ObjC || family_of(name) != Family::Gcc {
+        return true;
+    }
+    // prints the full path when installed, the bare name otherwise
+    Command::new(command_for(name))
+        .arg("-print-prog-name=cc1obj")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|out| Path::new(String::from_utf8_lossy(&out.stdout).trim()).is_absolute())
+}
+
+// Names a compiler is commonly installed under, most specific first so that
+// "clang++" is not mistaken for "clang" and "g++" not for "c++".
+static CORE_NAMES: &[&str] = &[
+    "clang++", "clang", "g++", "gcc", "c++", "cc", "icpc", "icc", "zig", "tcc", "pcc", "cl",
+];
+
+/// Reduces a compiler path to the name of the tool it runs:
+/// `/usr/bin/x86_64-linux-gnu-g++-13` → `g++`, `C:\VS\cl.exe` → `cl`,
+/// `clang-17` → `clang`. Unrecognized names come back lowercased but otherwise intact.
+pub fn core_name(name: &str) -> String {
+    let file = Path::new(name)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(name)
+        .to_ascii_lowercase();
+    let mut base = file.strip_suffix(".exe").unwrap_or(&file);
+
+    // version suffix: clang-17, gcc-13, clang++-17.0
+    if let Some((head, tail)) = base.rsplit_once('-')
+        && !tail.is_empty()
+        && tail.chars().all(|c| c.is_ascii_digit() || c == '.')
+    {
+        base = head;
+    }
+
+    // target prefix: x86_64-linux-gnu-gcc, arm-none-eabi-g++
+    for &core in CORE_NAMES {
+        if base == core || base.ends_with(&format!("-{}", core)) {
+            return core.to_string();
+        }
+    }
+    base.to_string()
+}
+
+fn generic_definition(name: &str) -> CompilerDef {
+    let stem = Path::new(name)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(name);
+    builtin(
+        stem,
+        Family::Generic,
+        &[Lang::C, Lang::Cpp],
+        "-o {out}",
+        ExtraPosition::BeforeSource,
+        "",
+        "",
+        "--version",
+        100,
+        false,
+    )
+}
+
+/// The definition used to build a command line for `name`. Registered names
+/// are used as-is. Anything else (full paths, `gcc-13`, `x86_64-linux-gnu-g++`,
+/// `cc`) borrows the built-in entry of the tool it turns out to be: first by
+/// its basename, then, when that is ambiguous, by its `--version` banner.
+pub fn definition_for(name: &str) -> CompilerDef {
+    if let Some(def) = lookup(name) {
+        return def.clone();
+    }
+
+    let core = core_name(name);
+    let template = match lookup(&core) {
+        Some(def) if core != "cc" && core != "c++" => Some(def.clone()),
+        _ => {
+            // "cc", "c++" and unknown names: ask the compiler itself
+            let is_cpp = core.ends_with("++");
+            compiler_version::probe_family(name).and_then(|family| {
+                builtins().into_iter().find(|d| {
+                    d.family == family && d.supports(if is_cpp { Lang::Cpp } else { Lang::C })
+                })
+            })
+        }
+    };
+
+    let mut def = template.unwrap_or_else(|| generic_definition(name));
+    def.name = Path::new(name)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(name)
+        .to_string();
+    def.command = name.to_string();
+    def.detect = false;
+    def
+}
+
+pub fn family_of(name: &str) -> Family {
+    definition_for(name).family
+}
+
+/// A registered compiler found on disk, possibly under a versioned name
+/// (`clang-18`) or in a directory PATH does not reach (`/usr/lib/llvm-17/bin`).
+#[derive(Clone, Debug)]
+pub struct Installation {
+    pub path: PathBuf,
+    /// The registry entry it is an installation of, e.g. `clang` for `clang-18`.
+    pub core: &'static str,
+    pub family: Family,
+    pub on_path: bool,
+}
+
+impl Installation {
+    /// The name to select it with: the bare file name when PATH reaches it,
+    /// the full path otherwise.
+    pub fn selector(&self) -> String {
+        let file = self
+            .path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default();
+        if self.on_path {
+            file.to_string()
+        } else {
+            self.path.to_string_lossy().to_string()
+        }
+    }
+}
+
+/// The registry entry a file is an installation of, accepting the exact command
+/// name or the command with a version suffix (`gcc-12`, `clang++-17`).
+/// Target-prefixed cross compilers are left to `-c` and `crun.ini`.
+fn installation_of(file: &str) -> Option<&'static CompilerDef> {
+    let file = file.to_ascii_lowercase();
+    let stem = file.strip_suffix(".exe").unwrap_or(&file);
+    let base = match stem.rsplit_once('-') {
+        Some((head, tail))
+            if !tail.is_empty() && tail.chars().all(|c| c.is_ascii_digit() || c == '.') =>
+        {
+            head
+        }
+        _ => stem,
+    };
+    REGISTRY
+        .iter()
+        .find(|d| d.detect && d.command == d.name && d.command == base)
+}
+
+/// Every installation of an auto-detectable compiler in PATH and the
+/// well-known install prefixes, PATH entries first.
+pub fn discover_installations() -> Vec<Installation> {
+    find_commands_matching(|file| installation_of(file).is_some())
+        .into_iter()
+        .filter_map(|(path, on_path)| {
+            let file = path.file_name()?.to_str()?;
+            let def = installation_of(file)?;
+            Some(Installation {
+                core: def.name.as_str(),
+                family: def.family,
+                on_path,
+                path,
+            })
+        })
+        .collect()
+}
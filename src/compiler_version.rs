@@ -0,0 +1,356 @@
+use crate::compiler_registry::{self, Family};
+use crate::probe_cache;
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompilerVersion {
+    pub family: String,
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    pub target: String,
+}
+
+impl CompilerVersion {
+    fn parts(&self) -> [u32; 3] {
+        [self.major, self.minor, self.patch]
+    }
+}
+
+impl fmt::Display for CompilerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}.{}.{}",
+            self.family, self.major, self.minor, self.patch
+        )?;
+        if !self.target.is_empty() {
+            write!(f, " ({})", self.target)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VersionOp {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// A version requirement such as `>=12` or `=17.0`.
+///
+/// Only the components that were written are compared, so `>=12` accepts
+/// `12.1.0` and `=17` accepts any `17.x.y`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionReq {
+    pub op: VersionOp,
+    pub parts: Vec<u32>,
+}
+
+impl VersionReq {
+    pub fn matches(&self, version: &CompilerVersion) -> bool {
+        let have = &version.parts()[..self.parts.len().min(3)];
+        let ord = have.cmp(&self.parts[..have.len()]);
+        match self.op {
+            VersionOp::Eq => ord == Ordering::Equal,
+            VersionOp::Gt => ord == Ordering::Greater,
+            VersionOp::Ge => ord != Ordering::Less,
+            VersionOp::Lt => ord == Ordering::Less,
+            VersionOp::Le => ord != Ordering::Greater,
+        }
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self.op {
+            VersionOp::Eq => "=",
+            VersionOp::Gt => ">",
+            VersionOp::Ge => ">=",
+            VersionOp::Lt => "<",
+            VersionOp::Le => "<=",
+        };
+        let parts: Vec<String> = self.parts.iter().map(|p| p.to_string()).collect();
+        write!(f, "{}{}", op, parts.join("."))
+    }
+}
+
+/// A compiler name with an optional version requirement, e.g. `gcc>=12`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompilerSpec {
+    pub name: String,
+    pub req: Option<VersionReq>,
+}
+
+impl fmt::Display for CompilerSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.req {
+            Some(req) => write!(f, "{}{}", self.name, req),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+pub fn parse_version_req(text: &str) -> Result<VersionReq, String> {
+    let text = text.trim();
+    let (op, rest) = if let Some(r) = text.strip_prefix(">=") {
+        (VersionOp::Ge, r)
+    } else if let Some(r) = text.strip_prefix("<=") {
+        (VersionOp::Le, r)
+    } else if let Some(r) = text.strip_prefix("==") {
+        (VersionOp::Eq, r)
+    } else if let Some(r) = text.strip_prefix('>') {
+        (VersionOp::Gt, r)
+    } else if let Some(r) = text.strip_prefix('<') {
+        (VersionOp::Lt, r)
+    } else if let Some(r) = text.strip_prefix('=') {
+        (VersionOp::Eq, r)
+    } else {
+        return Err(format!("'{}' is not a version requirement", text));
+    };
+
+    let parts = rest
+        .trim()
+        .split('.')
+        .map(|p| p.parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid version '{}'", rest.trim()))?;
+    if parts.is_empty() || parts.len() > 3 {
+        return Err(format!("invalid version '{}'", rest.trim()));
+    }
+    Ok(VersionReq { op, parts })
+}
+
+/// Parses a `--compiler` value such as `gcc`, `gcc>=12`, `clang<18` or
+/// `clang@17` (shorthand for `clang=17`).
+pub fn parse_compiler_spec(text: &str) -> Result<CompilerSpec, String> {
+    let text = text.trim();
+    if let Some((name, version)) = text.split_once('@') {
+        if name.trim().is_empty() {
+            return Err(format!("'{}' is missing a compiler name", text));
+        }
+        let version = version.trim();
+        let req = if version.starts_with(['<', '>', '=']) {
+            parse_version_req(version)?
+        } else {
+            parse_version_req(&format!("={}", version))?
+        };
+        return Ok(CompilerSpec {
+            name: name.trim().to_string(),
+            req: Some(req),
+        });
+    }
+    match text.find(['<', '>', '=']) {
+        Some(0) => Err(format!("'{}' is missing a compiler name", text)),
+        Some(idx) => Ok(CompilerSpec {
+            name: text[..idx].trim().to_string(),
+            req: Some(parse_version_req(&text[idx..])?),
+        }),
+        None => Ok(CompilerSpec {
+            name: text.to_string(),
+            req: None,
+        }),
+    }
+}
+
+/// Oldest releases whose default language modes crun relies on
+/// (C11 for C, C++14 for C++). Older installs are skipped during auto-detection.
+pub fn minimum_supported(compiler: &str) -> Option<VersionReq> {
+    let parts = match compiler {
+        "gcc" => vec![5],
+        "g++" => vec![6],
+        "clang" | "clang++" => vec![6],
+        _ => return None,
+    };
+    Some(VersionReq {
+        op: VersionOp::Ge,
+        parts,
+    })
+}
+
+fn compiler_name(compiler: &str) -> String {
+    Path::new(compiler)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(compiler)
+        .to_ascii_lowercase()
+}
+
+fn command_output(compiler: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(compiler).args(args).output().ok()?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Some(text)
+}
+
+fn parse_family(name: &str, banner: &str) -> String {
+    let lower = banner.to_ascii_lowercase();
+    let family = if lower.contains("clang") {
+        "clang"
+    } else if lower.contains("microsoft") {
+        "msvc"
+    } else if lower.contains("intel") || lower.contains("(icc)") || lower.contains("(icpc)") {
+        "intel"
+    } else if lower.contains("free software foundation") || lower.contains("gcc") {
+        "gcc"
+    } else if lower.starts_with("tcc") {
+        "tcc"
+    } else if lower.starts_with("pcc") {
+        "pcc"
+    } else if name == "zig" {
+        "zig"
+    } else {
+        name
+    };
+    family.to_string()
+}
+
+/// Compiler family named in the `--version` banner of `command`, for
+/// compilers whose name alone does not tell (`cc`, `c++`, wrapper scripts).
+pub fn probe_family(command: &str) -> Option<Family> {
+    let value = probe_cache::cached_or_probe(command, "family", || {
+        match command_output(command, &["--version"]) {
+            Some(banner) if !banner.trim().is_empty() => {
+                parse_family(&compiler_name(command), &banner)
+            }
+            _ => "unknown".to_string(),
+        }
+    });
+    Family::parse(&value)
+}
+
+/// Picks the first dotted number (`13.2.0`, `19.38.33134`, `0.11.0-dev`)
+/// out of a version banner.
+fn parse_version_numbers(banner: &str) -> Option<[u32; 3]> {
+    for line in banner.lines().filter(|l| !l.trim().is_empty()) {
+        for token in line.split(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+            if !token.starts_with(|c: char| c.is_ascii_digit()) || !token.contains('.') {
+                continue;
+            }
+            let mut parts = [0u32; 3];
+            let numbers = token
+                .split(|c: char| !c.is_ascii_digit() && c != '.')
+                .next()
+                .unwrap_or("");
+            for (slot, part) in parts.iter_mut().zip(numbers.split('.')) {
+                *slot = part.parse().unwrap_or(0);
+            }
+            return Some(parts);
+        }
+    }
+    None
+}
+
+fn parse_target(compiler: &str, family: &str, banner: &str) -> String {
+    // clang reports the target in its banner
+    if let Some(line) = banner.lines().find(|l| l.starts_with("Target:")) {
+        return line["Target:".len()..].trim().to_string();
+    }
+
+    match family {
+        "gcc" | "intel" | "clang" => {
+            let out = command_output(compiler, &["-dumpmachine"]).unwrap_or_default();
+            out.lines().next().unwrap_or("").trim().to_string()
+        }
+        "zig" => {
+            let out = command_output(compiler, &["cc", "-dumpmachine"]).unwrap_or_default();
+            out.lines().next().unwrap_or("").trim().to_string()
+        }
+        "msvc" => {
+            // "... Version 19.38.33134 for x64"
+            let arch = banner
+                .lines()
+                .next()
+                .and_then(|l| l.rsplit(" for ").next())
+                .unwrap_or("")
+                .trim();
+            match arch.to_ascii_lowercase().as_str() {
+                "x64" => "x86_64-pc-windows-msvc".to_string(),
+                "x86" | "80x86" => "i686-pc-windows-msvc".to_string(),
+                "arm64" => "aarch64-pc-windows-msvc".to_string(),
+                _ => String::new(),
+            }
+        }
+        "tcc" => {
+            // "tcc version 0.9.27 (x86_64 Linux)"
+            let first = banner.lines().next().unwrap_or("");
+            match (first.rfind('('), first.rfind(')')) {
+                (Some(start), Some(end)) if start < end => first[start + 1..end]
+                    .split_whitespace()
+                    .map(|s| s.to_ascii_lowercase())
+                    .collect::<Vec<_>>()
+                    .join("-"),
+                _ => String::new(),
+            }
+        }
+        "pcc" => {
+            // "pcc 1.1.0 for x86_64-pc-linux-gnu"
+            let first = banner.lines().next().unwrap_or("");
+            first
+                .rsplit(" for ")
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_string()
+        }
+        _ => String::new(),
+    }
+}
+
+/// Runs the compiler's version probe and parses family, version and target triple.
+pub fn detect_compiler_version(compiler: &str) -> Option<CompilerVersion> {
+    let name = compiler_name(compiler);
+    let def = compiler_registry::definition_for(compiler);
+    let version_args: Vec<&str> = def.version_args.iter().map(|s| s.as_str()).collect();
+    let banner = command_output(&def.command, &version_args)?;
+    let [major, minor, patch] = parse_version_numbers(&banner)?;
+    let family = parse_family(&name, &banner);
+    let target = parse_target(&def.command, &family, &banner);
+
+    Some(CompilerVersion {
+        family,
+        major,
+        minor,
+        patch,
+        target,
+    })
+}
+
+fn encode(version: &Option<CompilerVersion>) -> String {
+    match version {
+        Some(v) => format!(
+            "{}|{}.{}.{}|{}",
+            v.family, v.major, v.minor, v.patch, v.target
+        ),
+        None => "unknown".to_string(),
+    }
+}
+
+fn decode(value: &str) -> Option<CompilerVersion> {
+    let mut fields = value.splitn(3, '|');
+    let family = fields.next()?.to_string();
+    let [major, minor, patch] = parse_version_numbers(fields.next()?)?;
+    let target = fields.next().unwrap_or("").to_string();
+    Some(CompilerVersion {
+        family,
+        major,
+        minor,
+        patch,
+        target,
+    })
+}
+
+/// [`detect_compiler_version`] backed by the probe cache, so repeated runs do
+/// not have to start the compiler again.
+pub fn cached_compiler_version(compiler: &str) -> Option<CompilerVersion> {
+    let value = probe_cache::cached_or_probe(compiler, "version", || {
+        encode(&detect_compiler_version(compiler))
+    });
+    decode(&value)
+}
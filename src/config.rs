@@ -0,0 +1,77 @@
+use crate::fs_and_path_helpers::user_crun_dir;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// One `[kind name]` block of a config file, e.g. `[compiler arm-none-eabi-gcc]`.
+/// A header without a name (`[defaults]`) has an empty `name`.
+#[derive(Clone, Debug, Default)]
+pub struct Section {
+    pub kind: String,
+    pub name: String,
+    pub entries: Vec<(String, String)>,
+}
+
+static CONFIG: LazyLock<Vec<Section>> = LazyLock::new(load_config);
+
+/// Config files in the order they are applied: the per-user file first,
+/// then `crun.ini` in the current directory, so project settings win.
+pub fn config_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Some(dir) = user_crun_dir() {
+        files.push(dir.join("crun.ini"));
+    }
+    if let Ok(cwd) = std::env::current_dir() {
+        files.push(cwd.join("crun.ini"));
+    }
+    files
+}
+
+fn load_config() -> Vec<Section> {
+    let mut sections = Vec::new();
+    for file in config_files() {
+        if let Ok(content) = fs::read_to_string(&file) {
+            sections.extend(parse_config(&file, &content));
+        }
+    }
+    sections
+}
+
+/// Parses an INI-style file. Malformed lines are reported and skipped so a
+/// typo never stops a build.
+fn parse_config(file: &Path, content: &str) -> Vec<Section> {
+    let mut sections: Vec<Section> = Vec::new();
+    for (number, raw) in content.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let mut parts = header.trim().splitn(2, char::is_whitespace);
+            sections.push(Section {
+                kind: parts.next().unwrap_or("").to_string(),
+                name: parts.next().unwrap_or("").trim().to_string(),
+                entries: Vec::new(),
+            });
+            continue;
+        }
+
+        match (line.split_once('='), sections.last_mut()) {
+            (Some((key, value)), Some(section)) => section
+                .entries
+                .push((key.trim().to_string(), value.trim().to_string())),
+            _ => println!(
+                "{}:{}: ignoring line, expected `key = value` inside a [section]",
+                file.display(),
+                number + 1
+            ),
+        }
+    }
+    sections
+}
+
+/// All sections of the given kind, in the order they were read.
+pub fn sections(kind: &str) -> impl Iterator<Item = &'static Section> {
+    CONFIG.iter().filter(move |s| s.kind == kind)
+}
@@ -0,0 +1,173 @@
+use crate::command_exists::{command_exists, find_command};
+use crate::compiler_registry::{self, Family, Lang};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TargetOs {
+    Linux,
+    Windows,
+    MacOs,
+    Other,
+}
+
+/// A `--target` triple such as `aarch64-linux-gnu` or `x86_64-windows-gnu`.
+#[derive(Clone, Debug)]
+pub struct Target {
+    pub triple: String,
+    pub arch: String,
+    pub os: TargetOs,
+}
+
+pub fn is_windows_triple(triple: &str) -> bool {
+    let triple = triple.to_ascii_lowercase();
+    triple.contains("windows") || triple.contains("mingw") || triple.contains("win32")
+}
+
+pub fn parse_target(triple: &str) -> Result<Target, String> {
+    let triple = triple.trim();
+    let Some((arch, rest)) = triple.split_once('-') else {
+        return Err(format!(
+            "'{}' is not a target triple (expected e.g. aarch64-linux-gnu)",
+            triple
+        ));
+    };
+    let rest = rest.to_ascii_lowercase();
+    let os = if is_windows_triple(triple) {
+        TargetOs::Windows
+    } else if rest.contains("linux") {
+        TargetOs::Linux
+    } else if rest.contains("darwin") || rest.contains("macos") {
+        TargetOs::MacOs
+    } else {
+        TargetOs::Other
+    };
+    Ok(Target {
+        triple: triple.to_string(),
+        arch: arch.to_ascii_lowercase(),
+        os,
+    })
+}
+
+/// The architecture name qemu user-mode uses (`qemu-<arch>`), which is also
+/// how host and target architectures are compared.
+fn qemu_arch(arch: &str) -> &str {
+    match arch {
+        "amd64" | "x86_64" => "x86_64",
+        "x86" | "i386" | "i486" | "i586" | "i686" => "i386",
+        "arm64" | "aarch64" => "aarch64",
+        "powerpc" | "ppc" => "ppc",
+        "powerpc64" | "ppc64" => "ppc64",
+        "powerpc64le" | "ppc64le" => "ppc64le",
+        "riscv64gc" | "riscv64" => "riscv64",
+        a if a.starts_with("arm") || a.starts_with("thumb") => {
+            if a.ends_with("eb") {
+                "armeb"
+            } else {
+                "arm"
+            }
+        }
+        a => a,
+    }
+}
+
+pub fn host_os() -> TargetOs {
+    match std::env::consts::OS {
+        "linux" => TargetOs::Linux,
+        "windows" => TargetOs::Windows,
+        "macos" => TargetOs::MacOs,
+        _ => TargetOs::Other,
+    }
+}
+
+impl Target {
+    /// Whether the result can run directly on this machine.
+    pub fn is_native(&self) -> bool {
+        host_os() == self.os && qemu_arch(std::env::consts::ARCH) == qemu_arch(&self.arch)
+    }
+}
+
+/// Compilers to try for `target` when `-c` is not given: a cross-prefixed gcc
+/// toolchain first, then the drivers that cross-compile with a flag.
+pub fn cross_candidates(target: &Target, lang: Lang) -> Vec<String> {
+    let (gcc, clang) = match lang {
+        Lang::C | Lang::ObjC => ("gcc", "clang"),
+        Lang::Cpp => ("g++", "clang++"),
+        // other languages cross-compile with their own compiler's flags
+        _ => return Vec::new(),
+    };
+    vec![
+        format!("{}-{}", target.triple, gcc),
+        clang.to_string(),
+        "zig".to_string(),
+    ]
+}
+
+/// Picks the compiler to build for `target` with. gcc only builds for the
+/// target it was configured for, so a plain `gcc` is swapped for the
+/// `<triple>-gcc` toolchain.
+pub fn cross_compiler(compiler: &str, target: &Target) -> Result<String, String> {
+    if compiler_registry::family_of(compiler) != Family::Gcc {
+        return Ok(compiler.to_string());
+    }
+    let file = Path::new(compiler)
+        .file_name()
+        .and_then(|f| f.to_str())
+        .unwrap_or(compiler);
+    if file.starts_with(&format!("{}-", target.triple)) {
+        return Ok(compiler.to_string());
+    }
+    let prefixed = format!(
+        "{}-{}",
+        target.triple,
+        compiler_registry::core_name(compiler)
+    );
+    if command_exists(&prefixed) {
+        return Ok(prefixed);
+    }
+    Err(format!(
+        "'{}' cannot build for {}; install the {} toolchain or use clang or zig",
+        compiler, target.triple, prefixed
+    ))
+}
+
+/// The flags that make `compiler` build for `target`.
+pub fn target_args(compiler: &str, target: &Target) -> Result<Vec<String>, String> {
+    match compiler_registry::family_of(compiler) {
+        Family::Zig => Ok(vec!["-target".to_string(), target.triple.clone()]),
+        Family::Clang => Ok(vec![format!("--target={}", target.triple)]),
+        // cross-prefixed gcc toolchains build for their own triple by default
+        Family::Gcc => Ok(Vec::new()),
+        family => Err(format!(
+            "{} ({} family) does not support --target; use clang, zig or a cross gcc",
+            compiler,
+            family.as_str()
+        )),
+    }
+}
+
+/// The command prefix that runs a `target` binary on this machine:
+/// qemu user-mode for foreign Linux architectures, wine for Windows.
+pub fn find_runner(target: &Target) -> Option<Vec<String>> {
+    match target.os {
+        TargetOs::Linux if cfg!(target_os = "linux") => {
+            let arch = qemu_arch(&target.arch);
+            let qemu = [format!("qemu-{}", arch), format!("qemu-{}-static", arch)]
+                .into_iter()
+                .find(|q| command_exists(q))?;
+            let mut runner = vec![qemu];
+            // Debian/Ubuntu cross toolchains keep the target's loader and libc here
+            let sysroot = Path::new("/usr").join(&target.triple);
+            if sysroot.is_dir() {
+                runner.push("-L".to_string());
+                runner.push(sysroot.to_string_lossy().to_string());
+            }
+            Some(runner)
+        }
+        TargetOs::Windows if !cfg!(windows) => ["wine64", "wine"]
+            .into_iter()
+            .map(find_command)
+            .find(|w| !w.is_empty())
+            .map(|w| vec![w]),
+        _ => None,
+    }
+}
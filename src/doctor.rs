@@ -1,4 +1,7 @@
-use crate::command_exists::find_command;
+use crate::capabilities::{Capabilities, cached_capabilities, capabilities};
+use crate::compiler_registry::{self, Family, Installation, Lang};
+use crate::compiler_version::{CompilerVersion, cached_compiler_version};
+use crate::probe_cache::{self, parallel_map};
 
 pub fn log_pass(message: &str) {
     // [✓] with green ✓ and white brackets
@@ -13,6 +16,11 @@ pub fn log_critical(message: &str) {
     println!("{} \x1b[33m{}\x1b[0m", prefix, message); // yellow message
 }
 
+pub fn log_hint(message: &str) {
+    // indented under the failed check, cyan message
+    println!("    \x1b[36m↳ {}\x1b[0m", message);
+}
+
 pub fn log_fail(message: &str) {
     // [✗] with red ✗ and white brackets
     let cross = "\x1b[31m✗\x1b[0m"; // red cross
@@ -21,66 +29,140 @@ pub fn log_fail(message: &str) {
 }
 
 pub fn list_compilers(src_type: &str) {
-    let compilers: &[&str] = match src_type {
-        "c" => &["gcc", "clang", "zig", "cl", "icc", "tcc", "pcc"],
-        "cpp" => &["g++", "clang++", "cl", "icpc"],
-        "all" => &[
-            "gcc", "clang", "zig", "cl", "icc", "tcc", "pcc", "g++", "clang++", "icpc",
-        ],
-        _ => &[],
+    let lang = match src_type {
+        "all" => None,
+        other => match Lang::parse(other) {
+            Some(lang) => Some(lang),
+            None => {
+                let names: Vec<&str> = BACKENDS
+                    .iter()
+                    .filter(|b| b.doctor)
+                    .map(|b| b.lang.as_str())
+                    .collect();
+                println!(
+                    "Unknown language '{}' (expected {} or all)",
+                    other,
+                    names.join(", ")
+                );
+                return;
+            }
+        },
     };
 
-    for &compiler in compilers {
-        if !find_command(compiler).is_empty() {
-            println!("{}", compiler);
+    let installs: Vec<Installation> = compiler_registry::discover_installations()
+        .into_iter()
+        .filter(|i| {
+            let def = compiler_registry::lookup(i.core);
+            lang.is_none_or(|lang| {
+                def.is_some_and(|d| d.supports(lang))
+                    && compiler_registry::has_front_end(&i.selector(), lang)
+            })
+        })
+        .collect();
+    if installs.is_empty() {
+        println!("No compilers found");
+        return;
+    }
+
+    let rows = parallel_map(installs, |install| {
+        let selector = install.selector();
+        let version = cached_compiler_version(&selector);
+        let caps_lang = lang
+            .or_else(|| compiler_registry::lookup(install.core).map(|d| d.languages[0]))
+            .unwrap_or(Lang::C);
+        let standards = cached_capabilities(&selector, caps_lang)
+            .map(|caps| caps.standards().join(","))
+            .unwrap_or_default();
+        let mut location = install.path.display().to_string();
+        if !install.on_path {
+            location.push_str(" (not on PATH)");
+        }
+        [
+            install
+                .path
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            install.family.as_str().to_string(),
+            version
+                .as_ref()
+                .map(|v| format!("{}.{}.{}", v.major, v.minor, v.patch))
+                .unwrap_or_else(|| "?".to_string()),
+            version.map(|v| v.target).unwrap_or_default(),
+            standards,
+            location,
+        ]
+    });
+
+    let header = ["NAME", "FAMILY", "VERSION", "TARGET", "STANDARDS", "PATH"];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
         }
     }
+    let print_row = |cells: &[&str]| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(&header);
+    for row in &rows {
+        print_row(&row.each_ref().map(String::as_str));
+    }
+    println!();
+    println!("Select one with -c <name> or by version, e.g. -c clang@17 or -c \"gcc>=12\"");
 }
 
-use crate::compile_helpers::compile;
+use crate::assembly;
+use crate::backends::{BACKENDS, Backend};
+use crate::command_exists::{command_exists, find_command};
+use crate::compile_helpers::compile_captured;
+use crate::environment_checks::run_environment_checks;
+use crate::fs_and_path_helpers::user_crun_dir;
+use crate::install_hints::{Need, clang_alternative_hint, install_hint};
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-fn prepare_dummy_sources() -> (PathBuf, PathBuf) {
-    let home = if cfg!(windows) {
-        env::var("USERPROFILE").expect("USERPROFILE not set")
-    } else {
-        env::var("HOME").expect("HOME not set")
-    };
-
-    let base = Path::new(&home).join(".crun/doctor/dummy");
-    let _ = fs::create_dir_all(&base);
-
-    let c_path = base.join("dummy.c");
-    let cpp_path = base.join("dummy.cpp");
-
-    let c_code = r#"#include <stdio.h>
-int main() {
-    printf("Hello, C World!\n");
-    return 0;
-}
-"#;
-
-    let cpp_code = r#"#include <iostream>
-int main() {
-    std::cout << "Hello, C++ World!" << std::endl;
-    return 0;
+/// Writes each backend's hello-world template into `base` as `dummy.<ext>`,
+/// a scratch directory that `run_doctor` removes once it is done.
+fn prepare_dummy_sources(base: &Path, backends: &[&Backend]) -> std::io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(base)?;
+    let mut paths = Vec::new();
+    for backend in backends {
+        let ext = backend.extensions[0];
+        let path = base.join(format!("dummy.{}", ext));
+        fs::write(&path, backend.template(ext))?;
+        paths.push(path);
+    }
+    Ok(paths)
 }
-"#;
 
-    fs::write(&c_path, c_code).unwrap();
-    fs::write(&cpp_path, cpp_code).unwrap();
-
-    (c_path, cpp_path)
+/// Result of checking one compiler for one language.
+struct CompilerReport {
+    compiler: &'static str,
+    path: String,
+    works: bool,
+    // compiler output from a failed hello-world build, empty when cached
+    error: String,
+    version: Option<CompilerVersion>,
+    caps: Option<Capabilities>,
 }
 
-fn check_compiler_validity(compiler: &str, source: &Path) -> bool {
+fn check_compiler_validity(compiler: &str, source: &Path) -> (bool, String) {
     use std::process::Command;
 
-    let exe_path = source.with_extension("out");
+    // one executable per compiler, the checks run in parallel
+    let exe_path = source.with_file_name(format!(
+        "dummy-{}.out",
+        compiler.replace(['/', '\\', ':', '+'], "_")
+    ));
 
-    let status = compile(
+    let (status, output) = compile_captured(
         compiler,
         exe_path.to_str().unwrap(),
         source.to_str().unwrap(),
@@ -96,94 +178,267 @@ fn check_compiler_validity(compiler: &str, source: &Path) -> bool {
             .unwrap_or(false);
 
         let _ = fs::remove_file(&exe_path);
-        run_status
+        let error = if run_status {
+            String::new()
+        } else {
+            "the test program built but exited with an error".to_string()
+        };
+        (run_status, error)
     } else {
-        false
+        (false, output)
     }
 }
 
-pub(crate) fn run_doctor() {
-    static C_COMPILERS: &[&str] = &["gcc", "clang", "zig", "cl", "icc", "tcc", "pcc"];
-    static CPP_COMPILERS: &[&str] = &["g++", "clang++", "cl", "icpc"];
+fn probe_compiler(compiler: &'static str, lang: Lang, source: &Path) -> CompilerReport {
+    let kind = format!("works-{}", lang.as_str());
+    // only successes are cached: a failure is usually fixed by installing
+    // headers or a libc, which does not change the compiler's fingerprint
+    let (works, error) = match probe_cache::lookup(compiler, &kind) {
+        Some(value) if value == "yes" => (true, String::new()),
+        _ => {
+            let (works, error) = check_compiler_validity(compiler, source);
+            if works {
+                probe_cache::record(compiler, &kind, "yes");
+            }
+            (works, error)
+        }
+    };
+    CompilerReport {
+        compiler,
+        path: compiler_registry::resolve_path(compiler),
+        works,
+        error,
+        version: cached_compiler_version(compiler),
+        caps: (works && matches!(lang, Lang::C | Lang::Cpp)).then(|| capabilities(compiler, lang)),
+    }
+}
 
-    let (c_src, cpp_src) = prepare_dummy_sources();
+fn print_reports(reports: &[CompilerReport], lang_name: &str) {
+    for report in reports {
+        if !report.works {
+            log_fail(&format!(
+                "{} found but failed to compile/run {} code.",
+                report.compiler, lang_name
+            ));
+            for line in report.error.lines().take(3) {
+                println!("    {}", line);
+            }
+            continue;
+        }
+        match &report.version {
+            Some(version) => log_pass(&format!(
+                "{} found at {} [{}]",
+                report.compiler, report.path, version
+            )),
+            None => {
+                log_pass(&format!("{} found at {}", report.compiler, report.path));
+                log_critical(&format!(
+                    "could not determine the version of {}",
+                    report.compiler
+                ));
+            }
+        }
+    }
+}
 
-    println!("\x1b[1mRunning doctor...\n\x1b[0m");
+fn mark(ok: bool) -> &'static str {
+    if ok {
+        "\x1b[32m✓\x1b[0m"
+    } else {
+        "\x1b[31m✗\x1b[0m"
+    }
+}
 
-    println!("Checking for C compilers...");
-    let mut found_c = 0;
-    for &compiler in C_COMPILERS {
-        let path = find_command(compiler);
-        if !path.is_empty() {
-            if check_compiler_validity(compiler, &c_src) {
-                log_pass(&format!("{} found at {}", compiler, path));
-                found_c += 1;
+fn print_capability_header() {
+    println!(
+        "{:<10} {:<4} {:<30} {:<5} {:<5} {:<5} {:<7} {:<6} {:<6}",
+        "Compiler", "Lang", "Standards", "ASan", "UBSan", "TSan", "Threads", "OpenMP", "Static"
+    );
+}
+
+fn print_capability_row(compiler: &str, lang: Lang, caps: &Capabilities) {
+    let lang = crate::backends::for_lang(lang).name;
+    let standards = caps.standards().join(",");
+    // the marks are wrapped in colour codes, so pad them by hand
+    let cell = |feature: &str, width: usize| {
+        format!("{}{}", mark(caps.supports(feature)), " ".repeat(width - 1))
+    };
+    println!(
+        "{:<10} {:<4} {:<30} {} {} {} {} {} {}",
+        compiler,
+        lang,
+        if standards.is_empty() {
+            "-"
+        } else {
+            &standards
+        },
+        cell("asan", 5),
+        cell("ubsan", 5),
+        cell("tsan", 5),
+        cell("threads", 7),
+        cell("openmp", 6),
+        cell("static", 6),
+    );
+    let headers = caps.headers();
+    if lang == "C++" {
+        println!(
+            "{:<15} headers: {}",
+            "",
+            if headers.is_empty() {
+                "-".to_string()
             } else {
-                log_fail(&format!(
-                    "{} found but failed to compile/run C code.",
-                    compiler
-                ));
+                headers.join(" ")
             }
+        );
+    }
+}
+
+/// `.s`/`.S` files go through a working gcc-style driver; `.asm` files need nasm or yasm.
+fn check_assemblers(c_reports: &[CompilerReport]) {
+    let gas_driver = c_reports.iter().find(|r| {
+        r.works
+            && matches!(
+                compiler_registry::family_of(r.compiler),
+                Family::Gcc | Family::Clang | Family::Zig | Family::Intel
+            )
+    });
+    match gas_driver {
+        Some(report) => log_pass(&format!(".s/.S files are assembled by {}", report.compiler)),
+        None => log_critical(".s/.S files need gcc or clang, and neither works"),
+    }
+
+    match assembly::find_assembler() {
+        Some(assembler) => {
+            log_pass(&format!(
+                "{} found at {} (for .asm files)",
+                assembler,
+                find_command(assembler)
+            ));
+            if cfg!(target_os = "linux") && !command_exists("ld") {
+                log_critical("ld not found; .asm programs that start at _start cannot be linked");
+                log_hint(&install_hint(Need::CCompiler));
+            }
+        }
+        None => {
+            log_critical("Neither nasm nor yasm found; .asm files cannot be built");
+            log_hint(&install_hint(Need::Nasm));
         }
     }
-    if found_c == 0 {
-        log_fail("No working C compilers found.");
+}
+
+pub(crate) fn run_doctor() {
+    let c_compilers = compiler_registry::candidate_names(Lang::C);
+    let cpp_compilers = compiler_registry::candidate_names(Lang::Cpp);
+    let backends: Vec<&Backend> = BACKENDS.iter().filter(|b| b.doctor).collect();
+
+    println!("\x1b[1mRunning doctor...\n\x1b[0m");
+
+    // Older versions left their dummy sources here and never removed them
+    if let Some(dir) = user_crun_dir() {
+        let _ = fs::remove_dir_all(dir.join("doctor"));
     }
 
-    println!("\nChecking for C++ compilers...");
-    let mut found_cpp = 0;
-    for &compiler in CPP_COMPILERS {
-        let path = find_command(compiler);
-        if !path.is_empty() {
-            if check_compiler_validity(compiler, &cpp_src) {
-                // let version = detect_compiler_version(compiler);
-                log_pass(&format!("{} found at {}", compiler, path));
-                found_cpp += 1;
+    let scratch = env::temp_dir().join(format!("crun-doctor-{}", std::process::id()));
+    let sources = match prepare_dummy_sources(&scratch, &backends) {
+        Ok(paths) => paths,
+        Err(e) => {
+            log_fail(&format!(
+                "Cannot write test sources to {}: {}",
+                scratch.display(),
+                e
+            ));
+            log_hint("Point TMPDIR (TEMP on Windows) at a writable directory and try again.");
+            return;
+        }
+    };
+
+    // every installed compiler is probed for each of its languages in
+    // parallel, then reported per backend in order
+    let mut jobs: Vec<(&'static str, usize)> = Vec::new();
+    // installed drivers missing the language's front end are reported, not probed
+    let mut without_front_end: Vec<(&'static str, usize)> = Vec::new();
+    for (index, backend) in backends.iter().enumerate() {
+        for compiler in compiler_registry::candidate_names(backend.lang) {
+            if compiler_registry::resolve_path(compiler).is_empty() {
+                continue;
+            }
+            if compiler_registry::has_front_end(compiler, backend.lang) {
+                jobs.push((compiler, index));
             } else {
-                log_fail(&format!(
-                    "{} found but failed to compile/run C code.",
-                    compiler
-                ));
+                without_front_end.push((compiler, index));
+            }
+        }
+    }
+    let reports = parallel_map(jobs, |(compiler, index)| {
+        let report = probe_compiler(compiler, backends[index].lang, &sources[index]);
+        (index, report)
+    });
+    let mut by_backend: Vec<Vec<CompilerReport>> = backends.iter().map(|_| Vec::new()).collect();
+    for (index, report) in reports {
+        by_backend[index].push(report);
+    }
+
+    for (index, (backend, reports)) in backends.iter().zip(&by_backend).enumerate() {
+        if backend.lang != Lang::C {
+            println!();
+        }
+        println!("Checking for {} compilers...", backend.name);
+        for (compiler, _) in without_front_end.iter().filter(|(_, i)| *i == index) {
+            log_critical(&format!(
+                "{} is installed without its {} support",
+                compiler, backend.name
+            ));
+        }
+        let need = match backend.lang {
+            Lang::C => Need::CCompiler,
+            Lang::Cpp => Need::CppCompiler,
+            // other languages are optional; only say what crun looks for
+            lang => {
+                if reports.is_empty() {
+                    log_critical(&format!(
+                        "No {} compiler found (looked for {})",
+                        backend.name,
+                        compiler_registry::candidate_names(lang).join(", ")
+                    ));
+                }
+                print_reports(reports, backend.name);
+                continue;
+            }
+        };
+        print_reports(reports, backend.name);
+        if !reports.iter().any(|r| r.works) {
+            log_fail(&format!("No working {} compilers found.", backend.name));
+            log_hint(&install_hint(need));
+            if let Some(hint) = clang_alternative_hint() {
+                log_hint(&hint);
             }
         }
     }
-    if found_cpp == 0 {
-        log_fail("No working C++ compilers found.");
+    let c_reports = &by_backend[0];
+
+    let working: Vec<(&CompilerReport, Lang)> = backends
+        .iter()
+        .zip(&by_backend)
+        .filter(|(b, _)| matches!(b.lang, Lang::C | Lang::Cpp))
+        .flat_map(|(b, reports)| reports.iter().map(|r| (r, b.lang)))
+        .filter(|(r, _)| r.works)
+        .collect();
+    if !working.is_empty() {
+        println!("\nCompiler capabilities...");
+        print_capability_header();
+        for (report, lang) in working {
+            if let Some(caps) = &report.caps {
+                print_capability_row(report.compiler, lang, caps);
+            }
+        }
     }
 
+    println!("\nChecking for assemblers...");
+    check_assemblers(c_reports);
+
+    println!("\nChecking environment...");
+    run_environment_checks(&c_compilers, &cpp_compilers, &scratch);
+
+    let _ = fs::remove_dir_all(&scratch);
     println!("\nDoctor finished.");
 }
-
-//
-// fn detect_compiler_version(compiler: &str) -> String {
-//     use std::process::Command;
-//
-//     let version_args = match compiler {
-//         "gcc" | "g++" | "clang" | "clang++" | "icc" | "icpc" => vec!["--version"],
-//         "cl" => vec!["/Bv"], // MSVC version
-//         "zig" => vec!["version"],
-//         "tcc" => vec!["-v"],
-//         "pcc" => vec!["-v"],
-//         "lcc" => vec!["-version"],
-//         "wcl" => vec!["-h"],
-//         "bcc32" => vec!["-v"],
-//         "dmc" => vec!["-v"],
-//         "sdcc" => vec!["--version"],
-//         _ => return "unknown".into(),
-//     };
-//
-//     let output = Command::new(compiler)
-//         .args(&version_args)
-//         .output()
-//         .ok()
-//         .and_then(|o| String::from_utf8(o.stdout).ok())
-//         .unwrap_or_default();
-//
-//     // Extract first line or fallback
-//     output
-//         .lines()
-//         .next()
-//         .unwrap_or("unknown")
-//         .trim()
-//         .to_string()
-// }
@@ -0,0 +1,384 @@
+use crate::command_exists::command_exists;
+use crate::compiler_registry::{self, Family, Lang};
+use crate::{LOG, run_command};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// The intermediate `--emit` asks for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmitKind {
+    Pp,
+    Asm,
+    Ir,
+    Obj,
+}
+
+impl EmitKind {
+    pub fn parse(text: &str) -> Option<EmitKind> {
+        match text {
+            "pp" => Some(EmitKind::Pp),
+            "asm" => Some(EmitKind::Asm),
+            "ir" => Some(EmitKind::Ir),
+            "obj" => Some(EmitKind::Obj),
+            _ => None,
+        }
+    }
+
+    fn extension(&self, family: Family, lang: Lang) -> &'static str {
+        match (self, family == Family::Msvc) {
+            (EmitKind::Pp, _) if lang == Lang::Cpp => "ii",
+            (EmitKind::Pp, _) => "i",
+            (EmitKind::Asm, true) => "asm",
+            (EmitKind::Asm, false) => "s",
+            (EmitKind::Ir, _) => "ll",
+            (EmitKind::Obj, true) => "obj",
+            (EmitKind::Obj, false) => "o",
+        }
+    }
+}
+
+/// Arguments that make `family` stop at `kind` and write it to `out`.
+fn mode_args(kind: EmitKind, family: Family, out: &str) -> Result<Vec<String>, String> {
+    let args = match (kind, family) {
+        (EmitKind::Pp, Family::Msvc) => vec!["/P".to_string(), format!("/Fi{}", out)],
+        // /FA writes the listing next to the object, so point both into .crun
+        (EmitKind::Asm, Family::Msvc) => vec![
+            "/c".to_string(),
+            "/FA".to_string(),
+            format!("/Fa{}", out),
+            format!("/Fo{}", Path::new(out).with_extension("obj").display()),
+        ],
+        (EmitKind::Obj, Family::Msvc) => vec!["/c".to_string(), format!("/Fo{}", out)],
+        (EmitKind::Ir, Family::Clang | Family::Zig) => {
+            vec!["-S".into(), "-emit-llvm".into(), "-o".into(), out.into()]
+        }
+        (EmitKind::Ir, family) => {
+            return Err(format!(
+                "LLVM IR needs clang or zig; {} family compilers cannot emit it",
+                family.as_str()
+            ));
+        }
+        (EmitKind::Pp, _) => vec!["-E".into(), "-o".into(), out.into()],
+        (EmitKind::Asm, _) => vec!["-S".into(), "-o".into(), out.into()],
+        (EmitKind::Obj, _) => vec!["-c".into(), "-o".into(), out.into()],
+    };
+    Ok(args)
+}
+
+/// The full argument list that runs `compiler` up to `kind` on `source`.
+fn emit_args(
+    compiler: &str,
+    source: &str,
+    extra: &str,
+    kind: EmitKind,
+    out: &str,
+) -> Result<Vec<String>, String> {
+    let def = compiler_registry::definition_for(compiler);
+    let mut args = def.prefix_args.clone();
+    args.extend(def.default_args.iter().cloned());
+    args.extend(extra.split_whitespace().map(String::from));
+    args.extend(mode_args(kind, def.family, out)?);
+    args.push(source.to_string());
+    Ok(args)
+}
+
+/// Runs `compiler` up to `kind` on `source`, writing the result to `out`.
+fn emit_one(
+    compiler: &str,
+    source: &str,
+    extra: &str,
+    kind: EmitKind,
+    out: &str,
+) -> Result<(), String> {
+    let args = emit_args(compiler, source, extra, kind, out)?;
+    let arg_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+    if run_command(compiler_registry::command_for(compiler), &arg_refs) {
+        Ok(())
+    } else {
+        Err(format!("{} failed to produce {}", compiler, out))
+    }
+}
+
+/// Compiles `source` to the object file `out` without linking.
+pub fn compile_object(compiler: &str, source: &str, extra: &str, out: &str) -> Result<(), String> {
+    emit_one(compiler, source, extra, EmitKind::Obj, out)
+}
+
+/// Same as [`compile_object`], but returns whether it succeeded and the
+/// compiler's combined output instead of printing it.
+pub fn compile_object_captured(
+    compiler: &str,
+    source: &str,
+    extra: &str,
+    out: &str,
+) -> Result<(bool, String), String> {
+    let args = emit_args(compiler, source, extra, EmitKind::Obj, out)?;
+    let output = Command::new(compiler_registry::command_for(compiler))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Cannot run {}: {}", compiler, e))?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok((output.status.success(), text))
+}
+
+/// What `--emit` was asked to do.
+pub struct EmitRequest<'a> {
+    pub kind: EmitKind,
+    /// Two flag sets to compare side by side, e.g. `["-O0", "-O2"]`.
+    pub compare: &'a [String],
+    pub pager: bool,
+}
+
+/// Writes the requested intermediate of `source` into `build_dir` and shows
+/// it (or the `--compare` diff) when a pager is requested.
+pub fn emit(
+    compiler: &str,
+    source: &str,
+    build_dir: &str,
+    extra: &str,
+    request: &EmitRequest,
+) -> Result<(), String> {
+    let family = compiler_registry::family_of(compiler);
+    let lang = crate::compile_helpers::source_lang(source);
+    let ext = request.kind.extension(family, lang);
+    let stem = Path::new(source)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("out");
+
+    if request.compare.is_empty() {
+        let out = Path::new(build_dir).join(format!("{}.{}", stem, ext));
+        let out = out.to_string_lossy();
+        emit_one(compiler, source, extra, request.kind, &out)?;
+        LOG.lock()
+            .unwrap()
+            .println(&format_args!("Wrote {}", out), None);
+        if request.pager {
+            if request.kind == EmitKind::Obj {
+                println!("Object files are binary; inspect {} with objdump -d", out);
+            } else {
+                let text = fs::read_to_string(out.as_ref()).map_err(|e| e.to_string())?;
+                show(&text);
+            }
+        }
+        return Ok(());
+    }
+
+    let [left, right] = request.compare else {
+        return Err("--compare takes exactly two flag sets, e.g. --compare -O0 -O2".to_string());
+    };
+    if request.kind == EmitKind::Obj {
+        return Err("--compare works on text output: use pp, asm or ir".to_string());
+    }
+    let mut texts = Vec::new();
+    for flags in [left, right] {
+        let tag: String = flags
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let out = Path::new(build_dir).join(format!("{}-{}.{}", stem, tag.trim_matches('_'), ext));
+        let out = out.to_string_lossy();
+        emit_one(
+            compiler,
+            source,
+            &format!("{} {}", extra, flags),
+            request.kind,
+            &out,
+        )?;
+        LOG.lock()
+            .unwrap()
+            .println(&format_args!("Wrote {}", out), None);
+        let text = fs::read_to_string(out.as_ref()).map_err(|e| e.to_string())?;
+        texts.push(if request.kind == EmitKind::Asm {
+            strip_asm_noise(&text)
+        } else {
+            text
+        });
+    }
+
+    let diff = side_by_side(&texts[0], &texts[1], left, right);
+    if request.pager {
+        show(&diff);
+    } else {
+        print!("{}", diff);
+    }
+    Ok(())
+}
+
+/// Drops assembler bookkeeping (CFI, debug and metadata directives) that hides
+/// the instructions when two listings are compared.
+fn strip_asm_noise(text: &str) -> String {
+    text.lines()
+        .filter(|line| {
+            let t = line.trim_start();
+            !(t.starts_with(".cfi_")
+                || t.starts_with(".file")
+                || t.starts_with(".ident")
+                || t.starts_with(".loc")
+                || t.starts_with(".section\t.note")
+                || t.starts_with(".section .note")
+                || t.starts_with(".addrsig")
+                || t.is_empty())
+        })
+        .map(|l| format!("{}\n", l))
+        .collect()
+}
+
+/// Largest LCS table [`diff_lines`] builds (16 MB); bigger differences are listed unaligned.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+enum Row<'a> {
+    Same(&'a str),
+    Left(&'a str),
+    Right(&'a str),
+}
+
+/// Line diff of `a` and `b` via the longest common subsequence.
+fn diff_lines<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<Row<'a>> {
+    // preprocessed C++ is tens of thousands of lines, nearly all of them the
+    // same on both sides, so only the changed middle goes through the table
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (middle_a, middle_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut rows: Vec<Row> = a[..prefix].iter().map(|line| Row::Same(line)).collect();
+    if (middle_a.len() + 1).saturating_mul(middle_b.len() + 1) <= MAX_DIFF_CELLS {
+        rows.extend(lcs_rows(middle_a, middle_b));
+    } else {
+        LOG.lock().unwrap().detail(&format_args!(
+            "The listings differ in too many lines to align; showing the changed part unaligned"
+        ));
+        rows.extend(middle_a.iter().map(|line| Row::Left(line)));
+        rows.extend(middle_b.iter().map(|line| Row::Right(line)));
+    }
+    rows.extend(a[a.len() - suffix..].iter().map(|line| Row::Same(line)));
+    rows
+}
+
+/// Aligns `a` and `b` on their longest common subsequence.
+fn lcs_rows<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<Row<'a>> {
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut rows = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            rows.push(Row::Same(a[i]));
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            rows.push(Row::Left(a[i]));
+            i += 1;
+        } else {
+            rows.push(Row::Right(b[j]));
+            j += 1;
+        }
+    }
+    rows
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', "    ")
+}
+
+fn fit(text: &str, width: usize) -> String {
+    let mut out: String = expand_tabs(text).chars().take(width).collect();
+    let len = out.chars().count();
+    out.extend(std::iter::repeat_n(' ', width - len));
+    out
+}
+
+/// Two columns, `|` marking changed lines and `<`/`>` lines only on one side.
+fn side_by_side(a: &str, b: &str, left_title: &str, right_title: &str) -> String {
+    const WIDTH: usize = 60;
+    let a: Vec<&str> = a.lines().collect();
+    let b: Vec<&str> = b.lines().collect();
+    let rows = diff_lines(&a, &b);
+
+    let mut out = format!(
+        "{} | {}\n{}\n",
+        fit(left_title, WIDTH),
+        right_title,
+        "-".repeat(WIDTH * 2 + 3)
+    );
+    let mut idx = 0;
+    while idx < rows.len() {
+        match &rows[idx] {
+            Row::Same(line) => {
+                out.push_str(&format!("{}   {}\n", fit(line, WIDTH), expand_tabs(line)));
+                idx += 1;
+            }
+            _ => {
+                // pair a run of removed lines with the added lines that follow it
+                let mut left = Vec::new();
+                let mut right = Vec::new();
+                while let Some(Row::Left(line)) = rows.get(idx) {
+                    left.push(*line);
+                    idx += 1;
+                }
+                while let Some(Row::Right(line)) = rows.get(idx) {
+                    right.push(*line);
+                    idx += 1;
+                }
+                for k in 0..left.len().max(right.len()) {
+                    let row = match (left.get(k), right.get(k)) {
+                        (Some(l), Some(r)) => format!("{} | {}", fit(l, WIDTH), expand_tabs(r)),
+                        (Some(l), None) => format!("{} <", fit(l, WIDTH)),
+                        (None, Some(r)) => format!("{} > {}", fit("", WIDTH), expand_tabs(r)),
+                        (None, None) => unreachable!(),
+                    };
+                    out.push_str(row.trim_end());
+                    out.push('\n');
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Shows `text` through `$PAGER` (falling back to `less`, or `more` on Windows),
+/// or prints it when no pager is available.
+fn show(text: &str) {
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "more" } else { "less" }.to_string());
+    let mut words = pager.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let mut args: Vec<&str> = words.collect();
+    // keep less from clearing the screen or failing on a short file
+    if program == "less" && args.is_empty() {
+        args.extend(["-R", "-F", "-X"]);
+    }
+
+    if command_exists(program)
+        && let Ok(mut child) = Command::new(program)
+            .args(&args)
+            .stdin(Stdio::piped())
+            .spawn()
+    {
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(text.as_bytes());
+        }
+        let _ = child.wait();
+        return;
+    }
+    print!("{}", text);
+}
@@ -0,0 +1,251 @@
+use crate::command_exists::{find_all_commands, find_broken_links, find_outside_path};
+use crate::compile_helpers::compile_captured;
+use crate::compiler_registry::{Family, command_for, family_of, resolve_path};
+use crate::doctor::{log_critical, log_fail, log_hint, log_pass};
+use crate::install_hints::{Need, add_to_path_hint, install_hint};
+use crate::run_in_terminal::find_terminal;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub fn run_environment_checks(c_compilers: &[&str], cpp_compilers: &[&str], scratch: &Path) {
+    let mut all: Vec<&str> = c_compilers.to_vec();
+    for &c in cpp_compilers {
+        if !all.contains(&c) {
+            all.push(c);
+        }
+    }
+
+    check_build_dir();
+    check_terminal();
+    check_shadowed_compilers(&all);
+    check_broken_links(&all);
+    check_off_path_compilers(&all);
+    check_dev_headers(c_compilers, cpp_compilers, scratch);
+}
+
+/// `.crun` must accept new files and allow running them; a noexec mount
+/// makes every build look like it failed to start.
+fn check_build_dir() {
+    let dir = match env::current_dir() {
+        Ok(cwd) => cwd.join(".crun"),
+        Err(e) => {
+            log_fail(&format!("Cannot read the current directory: {}", e));
+            log_hint("cd into an existing directory you have access to and run crun again.");
+            return;
+        }
+    };
+    let existed = dir.exists();
+
+    if let Err(e) = fs::create_dir_all(&dir) {
+        log_fail(&format!("Cannot create {}: {}", dir.display(), e));
+        log_hint("Run crun from a directory you own, or use -d to place binaries elsewhere.");
+        return;
+    }
+
+    let (probe, script) = if cfg!(windows) {
+        (dir.join("crun-doctor-probe.bat"), "@exit /b 0\r\n")
+    } else {
+        (dir.join("crun-doctor-probe.sh"), "#!/bin/sh\nexit 0\n")
+    };
+
+    if let Err(e) = fs::write(&probe, script) {
+        log_fail(&format!("{} is not writable: {}", dir.display(), e));
+        log_hint(&format!(
+            "Fix its permissions (e.g. chmod u+w {}) or use -d to place binaries elsewhere.",
+            dir.display()
+        ));
+    } else {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = fs::set_permissions(&probe, fs::Permissions::from_mode(0o755));
+        }
+
+        match Command::new(&probe)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+        {
+            Ok(status) if status.success() => log_pass(&format!(
+                "{} is writable and allows running binaries",
+                dir.display()
+            )),
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                log_fail(&format!(
+                    "{} is writable but programs in it cannot be executed (noexec mount?)",
+                    dir.display()
+                ));
+                log_hint(
+                    "Use -d to build into a directory on an executable filesystem (e.g. -d ~/bin), or remount without noexec.",
+                );
+            }
+            Ok(_) | Err(_) => {
+                log_critical(&format!(
+                    "Could not verify that programs in {} can be executed",
+                    dir.display()
+                ));
+                log_hint("Check the directory permissions and any antivirus or sandbox rules.");
+            }
+        }
+        let _ = fs::remove_file(&probe);
+    }
+
+    if !existed {
+        let _ = fs::remove_dir(&dir);
+    }
+}
+
+fn check_terminal() {
+    match find_terminal() {
+        Some(term) => log_pass(&format!("Terminal for -ntw: {}", term)),
+        None => {
+            log_fail("No terminal emulator found, -ntw will not work.");
+            if cfg!(target_os = "macos") {
+                log_hint(
+                    "-ntw uses osascript to drive Terminal.app; make sure /usr/bin is on PATH.",
+                );
+            } else {
+                log_hint(
+                    "Install one (xterm, gnome-terminal, konsole, alacritty, ...) or run without -ntw.",
+                );
+            }
+        }
+    }
+}
+
+fn check_shadowed_compilers(compilers: &[&str]) {
+    let mut shadowed = false;
+    for &compiler in compilers {
+        let matches = find_all_commands(command_for(compiler));
+        if matches.len() < 2 {
+            continue;
+        }
+        shadowed = true;
+        let hidden: Vec<String> = matches[1..]
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect();
+        log_critical(&format!(
+            "{} resolves to {}, which shadows {}",
+            compiler,
+            matches[0].display(),
+            hidden.join(", ")
+        ));
+        log_hint(&format!(
+            "crun uses the first match. Reorder PATH, or pick one explicitly with -c \"{}\".",
+            hidden[0]
+        ));
+    }
+    if !shadowed {
+        log_pass("No compiler is shadowed by another PATH entry");
+    }
+}
+
+fn check_broken_links(compilers: &[&str]) {
+    let mut broken_any = false;
+    for &compiler in compilers {
+        for (link, target) in find_broken_links(command_for(compiler)) {
+            broken_any = true;
+            log_fail(&format!(
+                "{} is a broken symlink to {}",
+                link.display(),
+                target.display()
+            ));
+            log_hint(&format!(
+                "Remove it (rm {}) or reinstall the package that provides {}.",
+                link.display(),
+                compiler
+            ));
+        }
+    }
+    if !broken_any {
+        log_pass("No broken compiler symlinks on PATH");
+    }
+}
+
+/// Only reports a header as missing when the compiler names it in its
+/// error; other failures are already covered by the hello-world check.
+fn headers_available(compiler: &str, header: &str, ext: &str, scratch: &Path) -> bool {
+    let src = scratch.join(format!("headers.{}", ext));
+    let out = scratch.join(format!("headers-{}.i", ext));
+    if fs::write(&src, format!("#include <{}>\n", header)).is_err() {
+        return true; // nothing we can tell from here
+    }
+    let is_msvc = family_of(compiler) == Family::Msvc;
+    let preprocess = if is_msvc { "/E" } else { "-E" };
+    let (ok, output) = compile_captured(
+        compiler,
+        &out.to_string_lossy(),
+        &src.to_string_lossy(),
+        preprocess,
+    );
+    ok || !output.contains(header)
+}
+
+fn check_dev_headers(c_compilers: &[&str], cpp_compilers: &[&str], scratch: &Path) {
+    let mut missing = false;
+    for &compiler in c_compilers {
+        if resolve_path(compiler).is_empty() || headers_available(compiler, "stdio.h", "c", scratch)
+        {
+            continue;
+        }
+        missing = true;
+        log_fail(&format!("{} cannot find <stdio.h>", compiler));
+        log_hint(&c_headers_hint(compiler));
+    }
+    for &compiler in cpp_compilers {
+        if resolve_path(compiler).is_empty()
+            || headers_available(compiler, "iostream", "cpp", scratch)
+        {
+            continue;
+        }
+        missing = true;
+        log_fail(&format!("{} cannot find <iostream>", compiler));
+        log_hint(&cpp_headers_hint(compiler));
+    }
+    if !missing {
+        log_pass("C and C++ standard headers are available to every installed compiler");
+    }
+}
+
+fn c_headers_hint(compiler: &str) -> String {
+    if family_of(compiler) == Family::Msvc {
+        return "Run crun from a Developer Command Prompt so INCLUDE points at the Windows SDK."
+            .to_string();
+    }
+    install_hint(Need::CHeaders)
+}
+
+fn cpp_headers_hint(compiler: &str) -> String {
+    if family_of(compiler) == Family::Msvc {
+        return "Run crun from a Developer Command Prompt so INCLUDE points at the MSVC headers."
+            .to_string();
+    }
+    install_hint(Need::CppHeaders)
+}
+
+fn check_off_path_compilers(compilers: &[&str]) {
+    let mut found_any = false;
+    for &compiler in compilers {
+        for path in find_outside_path(command_for(compiler)) {
+            found_any = true;
+            let dir = path.parent().unwrap_or(Path::new(""));
+            log_critical(&format!(
+                "{} is installed at {} but that directory is not on PATH",
+                compiler,
+                path.display()
+            ));
+            log_hint(&add_to_path_hint(dir));
+            log_hint(&format!(
+                "Or use it without touching PATH: crun -c \"{}\" <file>",
+                path.display()
+            ));
+        }
+    }
+    if !found_any {
+        log_pass("No compilers hidden outside PATH in the usual install locations");
+    }
+}
@@ -0,0 +1,329 @@
+use crate::LOG;
+use crate::build_env::{BuildOption, BuildSettings, OptionKind};
+use crate::command_exists::command_exists;
+use crate::compile_helpers;
+use crate::compiler_registry::{self, Lang};
+use crate::toggles::{self, Toggles};
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// A change to the build that is known to fix a diagnosis.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fix {
+    Link(&'static str),
+    Threads,
+    Compiler(String),
+}
+
+impl Fix {
+    /// The crun flag that makes the same change.
+    pub fn flag(&self) -> String {
+        match self {
+            Fix::Link(lib) => format!("--link {}", lib),
+            Fix::Threads => "--threads".to_string(),
+            Fix::Compiler(compiler) => format!("-c {}", compiler),
+        }
+    }
+
+    /// Applies the fix to the current build, translating it for `compiler`.
+    pub fn apply(&self, compiler: &mut String, settings: &mut BuildSettings) -> Result<(), String> {
+        let source = format!("retry with {}", self.flag());
+        let (compile, link) = match self {
+            Fix::Link(lib) => {
+                let option = BuildOption {
+                    kind: OptionKind::Link,
+                    value: lib.to_string(),
+                    source: source.clone(),
+                };
+                compile_helpers::option_flags(compiler, &[option])?
+            }
+            Fix::Threads => {
+                let threads = Toggles {
+                    threads: true,
+                    ..Toggles::default()
+                };
+                let lang = compiler_registry::definition_for(compiler).languages[0];
+                let (mut compile, mut link) = toggles::toggle_flags(compiler, lang, &threads)?;
+                for setting in compile.iter_mut().chain(link.iter_mut()) {
+                    setting.source = source.clone();
+                }
+                (compile, link)
+            }
+            Fix::Compiler(cpp) => {
+                *compiler = cpp.clone();
+                (Vec::new(), Vec::new())
+            }
+        };
+        settings.compile_flags.extend(compile);
+        settings.link_flags.extend(link);
+        Ok(())
+    }
+}
+
+/// A plain-language explanation of a compiler or linker error.
+pub struct Diagnosis {
+    pub explanation: String,
+    pub fix: Option<Fix>,
+}
+
+const MATH_FUNCTIONS: &[&str] = &[
+    "sqrt", "cbrt", "pow", "exp", "exp2", "log", "log2", "log10", "sin", "cos", "tan", "asin",
+    "acos", "atan", "atan2", "sinh", "cosh", "tanh", "floor", "ceil", "round", "trunc", "fmod",
+    "hypot", "fabs", "lround", "fmin", "fmax",
+];
+
+/// Headers declaring the functions beginners most often forget to include.
+const FUNCTION_HEADERS: &[(&str, &[&str])] = &[
+    (
+        "stdio.h",
+        &[
+            "printf", "scanf", "puts", "putchar", "getchar", "fgets", "fprintf", "sprintf",
+            "snprintf", "fopen", "fclose", "perror",
+        ],
+    ),
+    (
+        "stdlib.h",
+        &[
+            "malloc", "calloc", "realloc", "free", "exit", "atoi", "atof", "rand", "srand",
+            "qsort", "abs",
+        ],
+    ),
+    (
+        "string.h",
+        &[
+            "strlen", "strcpy", "strncpy", "strcmp", "strncmp", "strcat", "strchr", "strstr",
+            "memcpy", "memset", "memmove", "memcmp",
+        ],
+    ),
+    ("ctype.h", &["isalpha", "isdigit", "isspace", "toupper", "tolower"]),
+    ("time.h", &["time", "clock"]),
+];
+
+/// Symbols the linker could not resolve, in the spellings of GNU ld
+/// (undefined reference to `sqrt'), lld (undefined symbol: sqrt), Apple ld
+/// ("_sqrt", referenced from:) and link.exe (unresolved external symbol sqrt).
+fn undefined_symbols(output: &str) -> Vec<String> {
+    let mut symbols = Vec::new();
+    for line in output.lines() {
+        // Mach-O and 32-bit Windows prefix C symbols with an underscore
+        let (symbol, prefixed) = if let Some(rest) = line.split("undefined reference to `").nth(1) {
+            (rest.split('\'').next(), false)
+        } else if let Some(rest) = line.split("undefined symbol: ").nth(1) {
+            (Some(rest.trim()), false)
+        } else if let Some(rest) = line.split("unresolved external symbol ").nth(1) {
+            (rest.split_whitespace().next(), true)
+        } else if line.trim_end().ends_with("referenced from:") {
+            (line.split('"').nth(1), true)
+        } else {
+            (None, false)
+        };
+        if let Some(symbol) = symbol {
+            let symbol = match symbol.strip_prefix('_') {
+                Some(stripped) if prefixed => stripped,
+                _ => symbol,
+            };
+            let symbol = symbol.to_string();
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
+        }
+    }
+    symbols
+}
+
+/// Functions used without a declaration, as gcc and clang word it.
+fn undeclared_functions(output: &str) -> Vec<String> {
+    let phrases = [
+        "implicit declaration of function ",
+        "implicitly declaring library function ",
+        "call to undeclared library function ",
+        "call to undeclared function ",
+    ];
+    let mut names = Vec::new();
+    for line in output.lines() {
+        let Some(rest) = phrases.iter().find_map(|p| line.split(p).nth(1)) else {
+            continue;
+        };
+        let name: String = rest
+            .trim_start_matches(['\'', '‘', '`', '"'])
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+fn is_math_function(symbol: &str) -> bool {
+    MATH_FUNCTIONS.contains(&symbol)
+        || symbol
+            .strip_suffix(['f', 'l'])
+            .is_some_and(|s| MATH_FUNCTIONS.contains(&s))
+}
+
+fn is_thread_function(symbol: &str) -> bool {
+    ["pthread_", "thrd_", "mtx_", "cnd_"]
+        .iter()
+        .any(|p| symbol.starts_with(p))
+}
+
+fn is_cpp_runtime_symbol(symbol: &str) -> bool {
+    symbol.contains("std::")
+        || symbol.starts_with("operator new")
+        || symbol.starts_with("operator delete")
+        || symbol.starts_with("vtable for")
+        || symbol.starts_with("__gxx_personality")
+        || symbol.starts_with("__cxa_")
+        || symbol.starts_with("_ZNSt")
+}
+
+/// The C++ driver of the same family as `compiler` (g++ for gcc, clang++ for clang).
+fn cpp_counterpart(compiler: &str) -> Option<String> {
+    let family = compiler_registry::family_of(compiler);
+    compiler_registry::candidate_names(Lang::Cpp)
+        .into_iter()
+        .find(|name| {
+            compiler_registry::family_of(name) == family
+                && command_exists(compiler_registry::command_for(name))
+        })
+        .map(String::from)
+}
+
+/// Matches `output` against the known mistakes.
+pub fn diagnose(output: &str, compiler: &str, source: &str) -> Vec<Diagnosis> {
+    let mut found = Vec::new();
+    let symbols = undefined_symbols(output);
+
+    if let Some(symbol) = symbols.iter().find(|s| is_math_function(s)) {
+        found.push(Diagnosis {
+            explanation: format!(
+                "`{}` is in the math library, which {} does not link unless asked to",
+                symbol, compiler
+            ),
+            fix: Some(Fix::Link("m")),
+        });
+    }
+    if let Some(symbol) = symbols.iter().find(|s| is_thread_function(s)) {
+        found.push(Diagnosis {
+            explanation: format!(
+                "`{}` is in the threads library, which needs -pthread to be linked",
+                symbol
+            ),
+            fix: Some(Fix::Threads),
+        });
+    }
+    if symbols.iter().any(|s| s == "main" || s == "WinMain") {
+        found.push(Diagnosis {
+            explanation: format!(
+                "{} has no `main` function, so there is nothing to run. Check its spelling, or use 'crun check {}' or 'crun build --lib static {}' for code without one",
+                source, source, source
+            ),
+            fix: None,
+        });
+    }
+    if symbols.iter().any(|s| is_cpp_runtime_symbol(s)) {
+        let cpp = cpp_counterpart(compiler).filter(|cpp| cpp != compiler);
+        found.push(Diagnosis {
+            explanation: format!(
+                "{} is C++, but {} links it as C, without the C++ standard library",
+                source, compiler
+            ),
+            fix: cpp.map(Fix::Compiler),
+        });
+    }
+    if source.ends_with(".c")
+        && (output.contains("iostream: No such file")
+            || output.contains("'iostream' file not found")
+            || output.contains("unknown type name 'class'")
+            || output.contains("unknown type name 'namespace'"))
+    {
+        found.push(Diagnosis {
+            explanation: format!(
+                "{} uses C++, but .c files are compiled as C; rename it to .cpp",
+                source
+            ),
+            fix: None,
+        });
+    }
+    for name in undeclared_functions(output) {
+        let explanation = match FUNCTION_HEADERS
+            .iter()
+            .find(|(_, functions)| functions.contains(&name.as_str()))
+        {
+            Some((header, _)) => format!(
+                "`{}` is used without being declared; add #include <{}> at the top of {}",
+                name, header, source
+            ),
+            None if is_math_function(&name) => format!(
+                "`{}` is used without being declared; add #include <math.h> at the top of {}",
+                name, source
+            ),
+            None => format!(
+                "`{}` is used without being declared; include the header that declares it, or declare it before its first use",
+                name
+            ),
+        };
+        found.push(Diagnosis {
+            explanation,
+            fix: None,
+        });
+    }
+    found
+}
+
+/// Asks whether to retry with `fix`. Without a terminal to ask on, nothing is retried.
+fn confirm(fix: &Fix) -> bool {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return false;
+    }
+    print!("Retry with {}? [Y/n] ", fix.flag());
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "" | "y" | "yes")
+}
+
+/// Drops the colour escape sequences compilers add for a terminal.
+fn strip_colours(output: &str) -> String {
+    let mut plain = String::with_capacity(output.len());
+    let mut chars = output.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // ESC [ parameters, ended by a letter
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Explains the `output` of a failed build and returns the fix to retry with
+/// when there is exactly one and the user agrees to it.
+pub fn explain_failure(output: &str, compiler: &str, source: &str) -> Option<Fix> {
+    let text = strip_colours(output);
+    let diagnoses = diagnose(&text, compiler, source);
+    let mut fixes: Vec<Fix> = Vec::new();
+    for diagnosis in &diagnoses {
+        let mut log = LOG.lock().unwrap();
+        log.println(&format_args!("Explanation: {}", diagnosis.explanation), None);
+        if let Some(fix) = &diagnosis.fix {
+            log.println(&format_args!("Fix: crun {} {}", fix.flag(), source), None);
+            if !fixes.contains(fix) {
+                fixes.push(fix.clone());
+            }
+        }
+    }
+    // several problems at once are left to the user rather than fixed one retry at a time
+    match fixes.as_slice() {
+        [fix] if confirm(fix) => Some(fix.clone()),
+        _ => None,
+    }
+}
@@ -1,3 +1,6 @@
+use crate::backends;
+use crate::cross_target::is_windows_triple;
+use crate::syntax_check::HEADER_EXTENSIONS;
 use crate::{Flags, LOG};
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -9,14 +12,23 @@ pub fn get_mod_time(path: &str) -> SystemTime {
         .unwrap_or(UNIX_EPOCH)
 }
 
+/// Per-user crun directory (`~/.crun`), used for caches shared between projects.
+pub fn user_crun_dir() -> Option<PathBuf> {
+    let home = if cfg!(windows) {
+        std::env::var_os("USERPROFILE")
+    } else {
+        std::env::var_os("HOME")
+    };
+    home.filter(|h| !h.is_empty())
+        .map(|h| PathBuf::from(h).join(".crun"))
+}
+
 fn must_make_dir(path: &str) {
     if let Err(e) = fs::create_dir_all(path) {
-        unsafe {
-            LOG.lock().unwrap().println(
-                &format_args!("Failed to create directory {}: {}", path, e),
-                None,
-            );
-        }
+        LOG.lock().unwrap().println(
+            &format_args!("Failed to create directory {}: {}", path, e),
+            None,
+        );
         process::exit(1);
     }
 }
@@ -25,14 +37,13 @@ pub fn find_source(file: &str) -> Option<String> {
     if Path::new(file).extension().is_some() {
         return Some(file.to_string());
     }
-    for ext in [".c", ".cpp", ".cc", ".cxx"] {
-        let candidate = format!("{}{}", file, ext);
+    let source_extensions = backends::BACKENDS.iter().flat_map(|b| b.extensions.iter());
+    for ext in source_extensions.chain(HEADER_EXTENSIONS) {
+        let candidate = format!("{}.{}", file, ext);
         if Path::new(&candidate).exists() {
-            unsafe {
-                LOG.lock()
-                    .unwrap()
-                    .println(&format_args!("No extension was provided, detected source file is '{}'. If incorrect, please specify the full filename.", candidate), None);
-            }
+            LOG.lock()
+                .unwrap()
+                .println(&format_args!("No extension was provided, detected source file is '{}'. If incorrect, please specify the full filename.", candidate), None);
             return Some(candidate);
         }
     }
@@ -46,7 +57,11 @@ pub fn setup_exe_path(flags: &Flags, src: &str, build_dir: &str) -> String {
     } else {
         flags.output_name.clone()
     };
-    if cfg!(windows) && !name.ends_with(".exe") {
+    // keep cross builds apart from the native binary
+    if !flags.target.is_empty() {
+        name = format!("{}-{}", name, flags.target);
+    }
+    if (cfg!(windows) || is_windows_triple(&flags.target)) && !name.ends_with(".exe") {
         name.push_str(".exe");
     }
     must_make_dir(build_dir);
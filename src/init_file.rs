@@ -1,3 +1,4 @@
+use crate::backends;
 use std::fs;
 use std::path::PathBuf;
 
@@ -22,29 +23,12 @@ pub fn init_source_file(name: &str) -> std::io::Result<()> {
     let ext = file_path
         .extension()
         .and_then(|e| e.to_str())
-        .unwrap_or("c")
-        .to_lowercase();
+        .unwrap_or("c");
 
-    let template = match ext.as_str() {
-        "cpp" | "cxx" | "cc" => {
-            r#"#include <iostream>
-
-int main() {
-    std::cout << "Hello from C++!" << std::endl;
-    return 0;
-}
-"#
-        }
-        _ => {
-            r#"#include <stdio.h>
-
-int main() {
-    printf("Hello from C!\n");
-    return 0;
-}
-"#
-        }
-    };
+    // files of an unknown type get the C template
+    let template = backends::for_source(&file_path.to_string_lossy())
+        .unwrap_or(&backends::BACKENDS[0])
+        .template(ext);
 
     fs::write(&file_path, template)?;
     println!("Created '{}'", file_path.display());
@@ -0,0 +1,98 @@
+use crate::compiler_registry::Family;
+use crate::install_hints::add_to_path_hint;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Optimization flags `crun install` builds with, ahead of any `-e` flags so those can override them.
+pub fn release_flags(family: Family) -> &'static str {
+    match family {
+        Family::Msvc => "/O2 /DNDEBUG",
+        _ => "-O2 -DNDEBUG",
+    }
+}
+
+/// Where the release build lives next to the regular one: `.crun/main` -> `.crun/main-release`.
+pub fn release_exe_path(exe: &str) -> String {
+    let path = Path::new(exe);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-release.{}", stem, ext.to_string_lossy()),
+        None => format!("{}-release", stem),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// `~/.local`, the default `--prefix`.
+pub fn default_prefix() -> Option<PathBuf> {
+    let home = if cfg!(windows) {
+        env::var_os("USERPROFILE")
+    } else {
+        env::var_os("HOME")
+    };
+    home.filter(|h| !h.is_empty())
+        .map(|h| PathBuf::from(h).join(".local"))
+}
+
+/// Expands a leading `~` the way a shell would, for `--prefix ~/tools` passed in quotes.
+fn expand_home(prefix: &str) -> PathBuf {
+    if let Some(rest) = prefix.strip_prefix("~")
+        && let Some(home) = default_prefix().and_then(|p| p.parent().map(Path::to_path_buf))
+    {
+        return home.join(rest.trim_start_matches(['/', '\\']));
+    }
+    PathBuf::from(prefix)
+}
+
+/// Copies `built` to `<prefix>/bin/<name>`. The copy is written next to the
+/// destination and renamed over it, so a running or concurrently started
+/// program never sees a half-written file.
+pub fn install(built: &str, name: &str, prefix: &str) -> Result<PathBuf, String> {
+    let prefix = if prefix.is_empty() {
+        default_prefix().ok_or("Cannot find the home directory; pass --prefix")?
+    } else {
+        expand_home(prefix)
+    };
+    let bin = prefix.join("bin");
+    fs::create_dir_all(&bin).map_err(|e| format!("Cannot create {}: {}", bin.display(), e))?;
+
+    let dest = bin.join(name);
+    let tmp = bin.join(format!(".{}.crun-tmp{}", name, std::process::id()));
+    fs::copy(built, &tmp).map_err(|e| format!("Cannot copy to {}: {}", tmp.display(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Err(e) = fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755)) {
+            let _ = fs::remove_file(&tmp);
+            return Err(format!("Cannot make {} executable: {}", tmp.display(), e));
+        }
+    }
+
+    // Windows refuses to rename over an existing file
+    if cfg!(windows) && dest.exists() {
+        let _ = fs::remove_file(&dest);
+    }
+    if let Err(e) = fs::rename(&tmp, &dest) {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("Cannot install {}: {}", dest.display(), e));
+    }
+    Ok(dest)
+}
+
+/// A hint for when `dir` is not on PATH, so the installed command cannot be run by name yet.
+pub fn path_hint(dir: &Path) -> Option<String> {
+    let on_path = env::var_os("PATH")
+        .map(|p| env::split_paths(&p).any(|d| d == dir))
+        .unwrap_or(false);
+    (!on_path).then(|| {
+        format!(
+            "{} is not on PATH. {}",
+            dir.display(),
+            add_to_path_hint(dir)
+        )
+    })
+}
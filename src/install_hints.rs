@@ -0,0 +1,208 @@
+use crate::command_exists::command_exists;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PackageManager {
+    Apt,
+    Dnf,
+    Pacman,
+    Zypper,
+    Apk,
+    Nix,
+    Brew,
+    Winget,
+}
+
+/// What the user is missing, so the hint can name the right package.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Need {
+    CCompiler,
+    CppCompiler,
+    Clang,
+    CHeaders,
+    CppHeaders,
+    Nasm,
+}
+
+/// The `ID` and `ID_LIKE` values of `/etc/os-release`, lowercased.
+fn os_release_ids() -> Vec<String> {
+    let content = fs::read_to_string("/etc/os-release")
+        .or_else(|_| fs::read_to_string("/usr/lib/os-release"))
+        .unwrap_or_default();
+    let mut ids = Vec::new();
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if key == "ID" || key == "ID_LIKE" {
+            ids.extend(
+                value
+                    .trim_matches('"')
+                    .split_whitespace()
+                    .map(|s| s.to_ascii_lowercase()),
+            );
+        }
+    }
+    ids
+}
+
+pub fn detect_package_manager() -> Option<PackageManager> {
+    if cfg!(windows) {
+        return Some(PackageManager::Winget);
+    }
+    if cfg!(target_os = "macos") {
+        return Some(PackageManager::Brew);
+    }
+
+    // A nix shell or NixOS manages compilers through nix even when the
+    // host distro has its own package manager.
+    if std::env::var_os("IN_NIX_SHELL").is_some() || Path::new("/etc/NIXOS").exists() {
+        return Some(PackageManager::Nix);
+    }
+
+    for id in os_release_ids() {
+        let pm = match id.as_str() {
+            "debian" | "ubuntu" | "linuxmint" | "pop" | "raspbian" | "kali" => PackageManager::Apt,
+            "fedora" | "rhel" | "centos" | "rocky" | "almalinux" | "amzn" => PackageManager::Dnf,
+            "arch" | "manjaro" | "endeavouros" => PackageManager::Pacman,
+            "opensuse" | "opensuse-leap" | "opensuse-tumbleweed" | "suse" | "sles" => {
+                PackageManager::Zypper
+            }
+            "alpine" => PackageManager::Apk,
+            "nixos" => PackageManager::Nix,
+            _ => continue,
+        };
+        return Some(pm);
+    }
+
+    // Unknown os-release: fall back to whichever package manager is installed
+    [
+        ("apt-get", PackageManager::Apt),
+        ("dnf", PackageManager::Dnf),
+        ("pacman", PackageManager::Pacman),
+        ("zypper", PackageManager::Zypper),
+        ("apk", PackageManager::Apk),
+        ("nix-env", PackageManager::Nix),
+    ]
+    .into_iter()
+    .find(|(cmd, _)| command_exists(cmd))
+    .map(|(_, pm)| pm)
+}
+
+/// The exact command that installs what is missing with the given package manager.
+pub fn install_command(pm: PackageManager, need: Need) -> &'static str {
+    use Need::*;
+    use PackageManager::*;
+    match (pm, need) {
+        (Apt, CCompiler) | (Apt, CppCompiler) => "sudo apt install build-essential",
+        (Apt, Clang) => "sudo apt install clang",
+        (Apt, CHeaders) => "sudo apt install libc6-dev",
+        (Apt, CppHeaders) => "sudo apt install g++",
+
+        (Dnf, CCompiler) => "sudo dnf install gcc",
+        (Dnf, CppCompiler) => "sudo dnf install gcc-c++",
+        (Dnf, Clang) => "sudo dnf install clang",
+        (Dnf, CHeaders) => "sudo dnf install glibc-devel",
+        (Dnf, CppHeaders) => "sudo dnf install libstdc++-devel",
+
+        (Pacman, CCompiler) | (Pacman, CppCompiler) => "sudo pacman -S --needed base-devel",
+        (Pacman, Clang) => "sudo pacman -S clang",
+        (Pacman, CHeaders) => "sudo pacman -S glibc linux-api-headers",
+        (Pacman, CppHeaders) => "sudo pacman -S gcc",
+
+        (Zypper, CCompiler) => "sudo zypper install gcc",
+        (Zypper, CppCompiler) => "sudo zypper install gcc-c++",
+        (Zypper, Clang) => "sudo zypper install clang",
+        (Zypper, CHeaders) => "sudo zypper install glibc-devel",
+        (Zypper, CppHeaders) => "sudo zypper install libstdc++-devel",
+
+        (Apk, CCompiler) | (Apk, CppCompiler) => "sudo apk add build-base",
+        (Apk, Clang) => "sudo apk add clang",
+        (Apk, CHeaders) => "sudo apk add musl-dev",
+        (Apk, CppHeaders) => "sudo apk add g++",
+
+        (Apt, Nasm) => "sudo apt install nasm",
+        (Dnf, Nasm) => "sudo dnf install nasm",
+        (Pacman, Nasm) => "sudo pacman -S nasm",
+        (Zypper, Nasm) => "sudo zypper install nasm",
+        (Apk, Nasm) => "sudo apk add nasm",
+        (Nix, Nasm) => "nix-shell -p nasm",
+        (Brew, Nasm) => "brew install nasm",
+        (Winget, Nasm) => "winget install NASM.NASM",
+
+        // the wrapped compilers bring their headers with them
+        (Nix, Clang) => "nix-shell -p clang",
+        (Nix, _) => "nix-shell -p gcc",
+
+        (Brew, Clang) => "brew install llvm",
+        (Brew, _) => "xcode-select --install",
+
+        (Winget, Clang) => "winget install LLVM.LLVM",
+        (Winget, _) => "winget install Microsoft.VisualStudio.2022.BuildTools",
+    }
+}
+
+fn manager_name(pm: PackageManager) -> &'static str {
+    match pm {
+        PackageManager::Apt => "apt",
+        PackageManager::Dnf => "dnf",
+        PackageManager::Pacman => "pacman",
+        PackageManager::Zypper => "zypper",
+        PackageManager::Apk => "apk",
+        PackageManager::Nix => "nix",
+        PackageManager::Brew => "Homebrew",
+        PackageManager::Winget => "winget",
+    }
+}
+
+/// A one-line remediation hint, e.g. "Install it with (dnf): sudo dnf install gcc-c++".
+pub fn install_hint(need: Need) -> String {
+    match detect_package_manager() {
+        Some(pm) => format!(
+            "Install it with ({}): {}",
+            manager_name(pm),
+            install_command(pm, need)
+        ),
+        None => match need {
+            Need::CCompiler | Need::CppCompiler | Need::Clang => {
+                "Install gcc or clang with your system's package manager.".to_string()
+            }
+            Need::CHeaders => {
+                "Install the C library development headers (libc6-dev, glibc-devel or musl-dev)."
+                    .to_string()
+            }
+            Need::CppHeaders => {
+                "Install the C++ standard library headers (libstdc++ dev package, or libc++-dev for clang)."
+                    .to_string()
+            }
+            Need::Nasm => {
+                "Install nasm (or yasm) with your system's package manager.".to_string()
+            }
+        },
+    }
+}
+
+/// Suggests installing clang as an alternative to the default gcc toolchain.
+pub fn clang_alternative_hint() -> Option<String> {
+    let pm = detect_package_manager()?;
+    Some(format!(
+        "Or use clang instead: {}",
+        install_command(pm, Need::Clang)
+    ))
+}
+
+/// How to put `dir` on PATH for the current shell and future ones.
+pub fn add_to_path_hint(dir: &Path) -> String {
+    if cfg!(windows) {
+        format!(
+            "Add it to PATH: setx PATH \"%PATH%;{}\" (then open a new terminal)",
+            dir.display()
+        )
+    } else {
+        format!(
+            "Add it to PATH: export PATH=\"{}:$PATH\" (put that line in ~/.bashrc or ~/.profile to keep it)",
+            dir.display()
+        )
+    }
+}
@@ -0,0 +1,259 @@
+use crate::command_exists::command_exists;
+use crate::compile_helpers::{self, compile};
+use crate::compiler_registry::{self, Family, Lang};
+use crate::cross_target::TargetOs;
+use crate::{LOG, emit, run_command};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LibKind {
+    Static,
+    Shared,
+}
+
+impl LibKind {
+    pub fn parse(text: &str) -> Option<LibKind> {
+        match text {
+            "static" => Some(LibKind::Static),
+            "shared" => Some(LibKind::Shared),
+            _ => None,
+        }
+    }
+}
+
+/// What `crun build --lib` was asked to produce.
+pub struct LibraryRequest<'a> {
+    pub kind: LibKind,
+    pub sources: &'a [String],
+    /// Base name of the library (`foo` for `libfoo.a`); the first source's stem when empty.
+    pub name: &'a str,
+    /// OS the library is built for, which decides file names and flags.
+    pub os: TargetOs,
+    /// Test program to link against the library and run; generated when missing.
+    pub driver: &'a str,
+}
+
+fn library_file_name(family: Family, kind: LibKind, os: TargetOs, base: &str) -> String {
+    match (family, kind, os) {
+        (Family::Msvc, LibKind::Static, _) => format!("{}.lib", base),
+        (Family::Msvc, LibKind::Shared, _) => format!("{}.dll", base),
+        (_, LibKind::Static, _) => format!("lib{}.a", base),
+        (_, LibKind::Shared, TargetOs::Windows) => format!("{}.dll", base),
+        (_, LibKind::Shared, TargetOs::MacOs) => format!("lib{}.dylib", base),
+        (_, LibKind::Shared, _) => format!("lib{}.so", base),
+    }
+}
+
+/// The archiver command (program and leading arguments) for static libraries.
+fn archiver(compiler: &str, family: Family) -> Result<Vec<String>, String> {
+    let command = compiler_registry::command_for(compiler).to_string();
+    let found = |tool: &str| command_exists(tool).then(|| tool.to_string());
+    let archiver = match family {
+        Family::Msvc => found("lib").map(|lib| vec![lib, "/NOLOGO".to_string()]),
+        Family::Zig => Some(vec![command, "ar".to_string(), "rcs".to_string()]),
+        Family::Tcc => Some(vec![command, "-ar".to_string(), "rcs".to_string()]),
+        Family::Clang => found("llvm-ar")
+            .or_else(|| found("ar"))
+            .map(|ar| vec![ar, "rcs".to_string()]),
+        _ => found("ar").map(|ar| vec![ar, "rcs".to_string()]),
+    };
+    archiver.ok_or_else(|| {
+        format!(
+            "No archiver found for {} ({} family); install binutils (ar) or use --lib shared",
+            compiler,
+            family.as_str()
+        )
+    })
+}
+
+fn run(command: &[String]) -> Result<(), String> {
+    let args: Vec<&str> = command[1..].iter().map(String::as_str).collect();
+    if run_command(&command[0], &args) {
+        Ok(())
+    } else {
+        Err(format!("{} failed", command[0]))
+    }
+}
+
+/// Writes a minimal program that includes the library's headers (`foo.h`
+/// next to `foo.c`) so it can be linked and run as a smoke test.
+fn generate_driver(path: &Path, sources: &[String], base: &str) -> std::io::Result<()> {
+    let headers: Vec<String> = sources
+        .iter()
+        .flat_map(|s| ["h", "hpp"].map(|ext| Path::new(s).with_extension(ext)))
+        .filter(|h| h.exists())
+        .map(|h| format!("#include \"{}\"\n", h.display()))
+        .collect();
+    let is_cpp = compile_helpers::source_lang(&path.to_string_lossy()) == Lang::Cpp;
+    let body = if is_cpp {
+        format!(
+            "#include <iostream>\n{}\nint main() {{\n    // call into the library here\n    std::cout << \"lib{} linked OK\" << std::endl;\n    return 0;\n}}\n",
+            headers.concat(),
+            base
+        )
+    } else {
+        format!(
+            "#include <stdio.h>\n{}\nint main(void) {{\n    /* call into the library here */\n    printf(\"lib{} linked OK\\n\");\n    return 0;\n}}\n",
+            headers.concat(),
+            base
+        )
+    };
+    fs::write(path, body)
+}
+
+/// Builds the driver against `library` and runs it.
+fn run_driver(
+    request: &LibraryRequest,
+    compiler: &str,
+    library: &Path,
+    build_dir: &Path,
+    extra: &str,
+    link: &str,
+    base: &str,
+) -> Result<(), String> {
+    let family = compiler_registry::family_of(compiler);
+    let driver = Path::new(request.driver);
+    if !driver.exists() {
+        generate_driver(driver, request.sources, base)
+            .map_err(|e| format!("Cannot write {}: {}", driver.display(), e))?;
+        println!("Generated test driver {}", driver.display());
+    }
+
+    let stem = driver
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "driver".to_string());
+    let mut exe = build_dir.join(stem);
+    if request.os == TargetOs::Windows {
+        exe.set_extension("exe");
+    }
+
+    // cl links against the import library that /LD writes next to the DLL
+    let mut link_args = if family == Family::Msvc {
+        library.with_extension("lib").display().to_string()
+    } else {
+        library.display().to_string()
+    };
+    if request.kind == LibKind::Shared && family != Family::Msvc && request.os == TargetOs::Linux {
+        link_args.push_str(&format!(" -Wl,-rpath,{}", build_dir.display()));
+    }
+    link_args.push(' ');
+    link_args.push_str(link);
+
+    let exe = exe.to_string_lossy();
+    if !compile(compiler, &exe, request.driver, extra, &[], &link_args, None).0 {
+        return Err(format!("Building {} failed", request.driver));
+    }
+    LOG.lock()
+        .unwrap()
+        .println(&format_args!("Running {}...", exe), None);
+    if !run_command(&exe, &[]) {
+        return Err(format!("{} exited with an error", exe));
+    }
+    Ok(())
+}
+
+/// Compiles every source to an object in `build_dir` and archives or links
+/// them into a static or shared library there. Returns the library path.
+pub fn build_library(
+    compiler: &str,
+    request: &LibraryRequest,
+    build_dir: &str,
+    extra: &str,
+    link: &str,
+) -> Result<PathBuf, String> {
+    let family = compiler_registry::family_of(compiler);
+    let first = request.sources.first().ok_or("No sources given")?;
+    let base = if request.name.is_empty() {
+        Path::new(first)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "lib".to_string())
+    } else {
+        request
+            .name
+            .strip_prefix("lib")
+            .unwrap_or(request.name)
+            .to_string()
+    };
+    if base.is_empty() {
+        return Err(format!(
+            "'{}' leaves no library name; use -o foo for libfoo",
+            request.name
+        ));
+    }
+    let build_dir = Path::new(build_dir);
+    let library = build_dir.join(library_file_name(family, request.kind, request.os, &base));
+
+    // shared objects need position-independent code outside Windows
+    let pic = request.kind == LibKind::Shared
+        && request.os != TargetOs::Windows
+        && !matches!(family, Family::Msvc);
+    let object_flags = if pic {
+        format!("{} -fPIC", extra)
+    } else {
+        extra.to_string()
+    };
+    let object_ext = if family == Family::Msvc { "obj" } else { "o" };
+
+    let mut objects = Vec::new();
+    for source in request.sources {
+        let stem = Path::new(source)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let object = build_dir.join(format!("{}-{}.{}", base, stem, object_ext));
+        let object = object.to_string_lossy().to_string();
+        emit::compile_object(compiler, source, &object_flags, &object)?;
+        objects.push(object);
+    }
+
+    let library_path = library.to_string_lossy().to_string();
+    let mut command: Vec<String>;
+    match (request.kind, family) {
+        (LibKind::Static, Family::Msvc) => {
+            command = archiver(compiler, family)?;
+            command.push(format!("/OUT:{}", library_path));
+            command.extend(objects);
+        }
+        (LibKind::Static, _) => {
+            // ar appends to an existing archive; start fresh so removed sources disappear
+            let _ = fs::remove_file(&library);
+            command = archiver(compiler, family)?;
+            command.push(library_path.clone());
+            command.extend(objects);
+        }
+        (LibKind::Shared, Family::Msvc) => {
+            command = vec![compiler_registry::command_for(compiler).to_string()];
+            command.push("/LD".to_string());
+            command.extend(objects);
+            command.push(format!("/Fe:{}", library_path));
+            if !link.trim().is_empty() {
+                command.push("/link".to_string());
+                command.extend(link.split_whitespace().map(String::from));
+            }
+        }
+        (LibKind::Shared, _) => {
+            let def = compiler_registry::definition_for(compiler);
+            command = vec![compiler_registry::command_for(compiler).to_string()];
+            command.extend(def.prefix_args.iter().cloned());
+            command.push(if request.os == TargetOs::MacOs {
+                "-dynamiclib".to_string()
+            } else {
+                "-shared".to_string()
+            });
+            command.push("-o".to_string());
+            command.push(library_path.clone());
+            command.extend(objects);
+            command.extend(link.split_whitespace().map(String::from));
+        }
+    }
+    run(&command)?;
+    println!("Built {}", library.display());
+
+    if !request.driver.is_empty() {
+        run_driver(request, compiler, &library, build_dir, extra, link, &base)?;
+    }
+    Ok(library)
+}
@@ -1,13 +1,35 @@
+mod assembly;
+mod auto_link;
+mod backends;
+mod build_env;
+mod cache_wrapper;
+mod capabilities;
 mod command_exists;
 mod compile_helpers;
+mod compiler_registry;
+mod compiler_version;
+mod config;
+mod cross_target;
 mod doctor;
+mod emit;
+mod environment_checks;
+mod explain;
 mod fs_and_path_helpers;
 mod init_file;
+mod install;
+mod install_hints;
+mod library;
+mod matrix;
+mod pch;
+mod probe_cache;
+mod remarks;
 mod run_in_terminal;
+mod size_report;
+mod syntax_check;
+mod toggles;
 mod ulog;
 
 use std::env;
-use std::io::Write;
 use std::path::Path;
 use std::process::{self, Command, Stdio};
 use std::sync::LazyLock;
@@ -29,6 +51,27 @@ struct Flags {
     check_only: bool,
     list_only: bool,
     list_for: String,
+    refresh: bool,
+    target: String,
+    emit: String,
+    compare: Vec<String>,
+    pager: bool,
+
+    subcommand: String,
+    remark_function: String,
+    remark_kind: String,
+    prefix: String,
+    cache_wrapper: String,
+    no_pch: bool,
+    no_auto_link: bool,
+    lib: String,
+    driver: String,
+    options: Vec<(build_env::OptionKind, String)>,
+    toggles: toggles::Toggles,
+    matrix_compilers: String,
+    matrix_stds: String,
+    matrix_opts: String,
+    input: String,
 
     init_only: bool,
     init_filename: String,
@@ -45,54 +88,45 @@ fn run_command(cmd: &str, args: &[&str]) -> bool {
         .unwrap_or(false)
 }
 
-fn run_binary(exe: &str, run_args: &str, flags: &Flags) {
-    let args: Vec<&str> = if run_args.is_empty() {
-        vec![]
-    } else {
-        run_args.split_whitespace().collect()
-    };
+/// Runs `exe` with the `-r` arguments, through `runner` (e.g. `qemu-aarch64`) when it is not empty.
+fn run_binary(exe: &str, runner: &[String], flags: &Flags) {
+    let mut command: Vec<&str> = runner.iter().map(String::as_str).collect();
+    command.push(exe);
+    command.extend(flags.run_args.split_whitespace());
+    let (program, args) = (command[0], &command[1..]);
 
     if flags.run_in_new_terminal {
-        unsafe {
-            LOG.lock()
-                .unwrap()
-                .println(&format_args!("Running in new terminal..."), None);
-        }
-        if let Err(e) = run_in_terminal::launch_in_external_terminal(
-            &exe,
-            &flags.run_args.split_whitespace().collect::<Vec<&str>>(),
-        ) {
-            unsafe {
-                LOG.lock().unwrap().println(
-                    &format_args!("Failed to launch in new terminal: {}", e),
-                    None,
-                );
-            }
+        LOG.lock()
+            .unwrap()
+            .println(&format_args!("Running in new terminal..."), None);
+        if let Err(e) = run_in_terminal::launch_in_external_terminal(program, args) {
+            LOG.lock().unwrap().println(
+                &format_args!("Failed to launch in new terminal: {}", e),
+                None,
+            );
         }
         return; // Prevent running the binary in the current terminal
     } else {
-        unsafe {
-            LOG.lock()
-                .unwrap()
-                .println(&format_args!("Running in current terminal..."), None);
+        LOG.lock()
+            .unwrap()
+            .println(&format_args!("Running in current terminal..."), None);
 
-            LOG.lock().unwrap().clear();
-        }
+        LOG.lock().unwrap().clear();
     }
 
     if !Path::new(exe).exists() {
-        unsafe {
-            LOG.lock()
-                .unwrap()
-                .println(&format_args!("Executable not found: {}", exe), None);
-        }
+        LOG.lock()
+            .unwrap()
+            .println(&format_args!("Executable not found: {}", exe), None);
         return;
     }
-    run_command(exe, &args);
+    run_command(program, args);
 }
 
 fn main() {
     let (flags, mut args) = parse_flags();
+    probe_cache::set_refresh(flags.refresh);
+    LOG.lock().unwrap().set_verbose(flags.verbose);
 
     if flags.list_only {
         doctor::list_compilers(flags.list_for.as_str());
@@ -112,11 +146,9 @@ fn main() {
     let src = match fs_and_path_helpers::find_source(&args.remove(0)) {
         Some(s) => s,
         None => {
-            unsafe {
-                LOG.lock()
-                    .unwrap()
-                    .println(&format_args!("No valid source file found"), None);
-            }
+            LOG.lock()
+                .unwrap()
+                .println(&format_args!("No valid source file found"), None);
             process::exit(1);
         }
     };
@@ -127,47 +159,424 @@ fn main() {
         .to_string_lossy()
         .to_string();
 
-    let exe = fs_and_path_helpers::setup_exe_path(&flags, &src, &build_dir);
+    let is_nasm = assembly::is_nasm_source(&src);
+    if is_nasm
+        && (!flags.emit.is_empty()
+            || !flags.lib.is_empty()
+            || matches!(flags.subcommand.as_str(), "check" | "remarks" | "matrix"))
+    {
+        exit_with(
+            "This mode is not available for .asm files; use a .S file to go through gcc or clang",
+        );
+    }
+
+    let checked = src.clone();
+    let src = if flags.subcommand == "check" {
+        syntax_check::translation_unit(&src, &build_dir).unwrap_or_else(|e| exit_with(&e))
+    } else if syntax_check::is_header(&src) {
+        exit_with(&format!(
+            "{} is a header and cannot be run; use 'crun check {}' to check it",
+            src, src
+        ));
+    } else {
+        src
+    };
+
+    let mut exe = fs_and_path_helpers::setup_exe_path(&flags, &src, &build_dir);
+    let install_name = Path::new(&exe)
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // release builds get their own binary so they never satisfy the cache of a debug run
+    if flags.subcommand == "install" {
+        exe = install::release_exe_path(&exe);
+    }
 
     let needs_recompile = flags.no_cache
         || fs_and_path_helpers::get_mod_time(&src) > fs_and_path_helpers::get_mod_time(&exe);
-    let compiler = compile_helpers::detect_compiler(&flags.compiler, &src);
+    let lang = compile_helpers::source_lang(&src);
+    let mut settings =
+        build_env::resolve(&flags.compiler, &flags.extra_flags, &flags.options, lang);
+    if flags.subcommand == "matrix" {
+        let request = matrix::MatrixRequest {
+            compilers: if flags.compiler.is_empty() {
+                &flags.matrix_compilers
+            } else {
+                &flags.compiler
+            },
+            stds: &matrix::split_list(&flags.matrix_stds),
+            opts: &matrix::split_list(&flags.matrix_opts),
+            input: &flags.input,
+            run_args: &flags.run_args,
+        };
+        let shared = matrix::SharedFlags {
+            compile: &settings.compile_flags_string(),
+            link: &settings.link_flags_string(),
+            options: &settings.options,
+            toggles: &flags.toggles,
+            auto_link: !flags.no_auto_link,
+        };
+        if let Err(e) = matrix::run(&src, lang, &build_dir, &request, &shared) {
+            exit_with(&e);
+        }
+        return;
+    }
+    let target = (!flags.target.is_empty())
+        .then(|| cross_target::parse_target(&flags.target).unwrap_or_else(|e| exit_with(&e)));
+    if let Some(target) = &target
+        && settings.compiler.is_none()
+    {
+        let candidates = cross_target::cross_candidates(target, lang);
+        settings.compiler = (!candidates.is_empty()).then(|| build_env::Setting {
+            value: candidates.join(","),
+            source: "--target".to_string(),
+        });
+    }
+    let mut compiler = compile_helpers::detect_compiler(
+        settings.compiler.as_ref().map_or("", |c| c.value.as_str()),
+        &src,
+    );
+    // .asm programs that start at _start only need the assembler and ld
+    if compiler.is_empty() && !is_nasm {
+        exit_with(&format!(
+            "No {} compiler found (looked for {}); run 'crun -check' for details",
+            backends::for_lang(lang).name,
+            compiler_registry::candidate_names(lang).join(", ")
+        ));
+    }
+    if settings.compiler.is_none()
+        && target.is_none()
+        && let Some(capable) = toggles::prefer_capable(&compiler, lang, &flags.toggles)
+    {
+        LOG.lock().unwrap().println(
+            &format_args!(
+                "{} lacks a requested feature, using {} instead",
+                compiler, capable
+            ),
+            None,
+        );
+        compiler = capable;
+    }
+    if let Some(target) = &target {
+        compiler =
+            cross_target::cross_compiler(&compiler, target).unwrap_or_else(|e| exit_with(&e));
+        let args = cross_target::target_args(&compiler, target).unwrap_or_else(|e| exit_with(&e));
+        if !args.is_empty() {
+            settings.compile_flags.insert(
+                0,
+                build_env::Setting {
+                    value: args.join(" "),
+                    source: "--target".to_string(),
+                },
+            );
+        }
+    }
+    let (option_compile, option_link) = compile_helpers::option_flags(&compiler, &settings.options)
+        .unwrap_or_else(|e| exit_with(&e));
+    settings.compile_flags.extend(option_compile);
+    settings.link_flags.extend(option_link);
+    let (toggle_compile, toggle_link) =
+        toggles::toggle_flags(&compiler, lang, &flags.toggles).unwrap_or_else(|e| exit_with(&e));
+    settings.compile_flags.extend(toggle_compile);
+    settings.link_flags.extend(toggle_link);
+    if !flags.no_auto_link {
+        let existing = format!(
+            "{} {}",
+            settings.compile_flags_string(),
+            settings.link_flags_string()
+        );
+        let (auto_compile, auto_link) = auto_link::link_flags(&compiler, lang, &src, &existing);
+        settings.compile_flags.extend(auto_compile);
+        settings.link_flags.extend(auto_link);
+    }
+    if assembly::is_gas_source(&src)
+        && let Some(link) = assembly::gas_link_flags(&src)
+    {
+        settings.link_flags.push(build_env::Setting {
+            value: link.to_string(),
+            source: "_start entry point".to_string(),
+        });
+    }
+    settings.wrapper = cache_wrapper::resolve(&flags.cache_wrapper, &compiler);
+    if flags.subcommand == "install" {
+        settings.compile_flags.insert(
+            0,
+            build_env::Setting {
+                value: install::release_flags(compiler_registry::family_of(&compiler)).to_string(),
+                source: "install".to_string(),
+            },
+        );
+    }
+    report_settings(&settings, &compiler);
+
+    if flags.subcommand == "check" {
+        match syntax_check::check(
+            &compiler,
+            &src,
+            &settings.compile_flags_string(),
+            &build_dir,
+        ) {
+            Ok(()) => println!("No errors in {}", checked),
+            Err(e) => exit_with(&e),
+        }
+        return;
+    }
 
-    if !needs_recompile {
-        unsafe {
-            LOG.lock().unwrap().println(&format_args!("No changes detected, skipping recompilation.Extra tip:: Use '-n' flag to always recompile if needed! "), None);
+    if flags.subcommand == "remarks" {
+        let filter = remarks::RemarkFilter {
+            function: &flags.remark_function,
+            kind: &flags.remark_kind,
+        };
+        if let Err(e) = remarks::show_remarks(
+            &compiler,
+            &src,
+            &exe,
+            &settings.compile_flags_string(),
+            &filter,
+        ) {
+            exit_with(&e);
         }
-        return run_binary(&exe, &flags.run_args, &flags);
+        return;
     }
 
-    unsafe {
-        LOG.lock()
-            .unwrap()
-            .println(&format_args!("Using compiler: {}", compiler), None);
+    if !flags.emit.is_empty() {
+        let Some(kind) = emit::EmitKind::parse(&flags.emit) else {
+            exit_with(&format!(
+                "Unknown --emit kind '{}' (expected pp, asm, ir or obj)",
+                flags.emit
+            ));
+        };
+        let request = emit::EmitRequest {
+            kind,
+            compare: &flags.compare,
+            pager: flags.pager,
+        };
+        if let Err(e) = emit::emit(
+            &compiler,
+            &src,
+            &build_dir,
+            &settings.compile_flags_string(),
+            &request,
+        ) {
+            exit_with(&e);
+        }
+        return;
     }
 
-    if !compile_helpers::compile(&compiler, &exe, &src, &flags.extra_flags) {
-        unsafe {
-            LOG.lock()
-                .unwrap()
-                .println(&format_args!("Compilation failed"), None);
+    if flags.subcommand == "build" && !flags.lib.is_empty() {
+        let Some(kind) = library::LibKind::parse(&flags.lib) else {
+            exit_with(&format!(
+                "Unknown --lib kind '{}' (expected static or shared)",
+                flags.lib
+            ));
+        };
+        let mut sources = vec![src];
+        for arg in &args {
+            match fs_and_path_helpers::find_source(arg) {
+                Some(s) => sources.push(s),
+                None => exit_with(&format!("No valid source file found for {}", arg)),
+            }
         }
-        process::exit(1);
+        let request = library::LibraryRequest {
+            kind,
+            sources: &sources,
+            name: &flags.output_name,
+            os: target.as_ref().map_or_else(cross_target::host_os, |t| t.os),
+            driver: &flags.driver,
+        };
+        if let Err(e) = library::build_library(
+            &compiler,
+            &request,
+            &build_dir,
+            &settings.compile_flags_string(),
+            &settings.link_flags_string(),
+        ) {
+            exit_with(&e);
+        }
+        return;
     }
 
-    unsafe {
+    // foreign binaries run through qemu or wine when one is installed
+    let runner: Vec<String> = match &target {
+        Some(target) if !target.is_native() => {
+            cross_target::find_runner(target).unwrap_or_default()
+        }
+        _ => Vec::new(),
+    };
+    let can_run = target.as_ref().is_none_or(|t| t.is_native()) || !runner.is_empty();
+
+    if needs_recompile {
+        let pch_args = if flags.no_pch {
+            Vec::new()
+        } else {
+            pch::prelude_args(&compiler, &src, &settings.compile_flags_string())
+                .unwrap_or_default()
+        };
+        if is_nasm {
+            // -e flags go to the assembler; CFLAGS and toggles are meant for the C compiler
+            let assembler_flags: Vec<&str> = settings
+                .compile_flags
+                .iter()
+                .filter(|f| f.source == "-e")
+                .map(|f| f.value.as_str())
+                .collect();
+            if let Err(e) = assembly::build_nasm(
+                &compiler,
+                &src,
+                &exe,
+                &assembler_flags.join(" "),
+                &settings.link_flags_string(),
+            ) {
+                exit_with(&e);
+            }
+        } else if let Err(output) = build(&compiler, &exe, &src, &settings, &pch_args) {
+            let Some(fix) = explain::explain_failure(&output, &compiler, &src) else {
+                process::exit(1);
+            };
+            fix.apply(&mut compiler, &mut settings)
+                .unwrap_or_else(|e| exit_with(&e));
+            report_settings(&settings, &compiler);
+            if build(&compiler, &exe, &src, &settings, &pch_args).is_err() {
+                process::exit(1);
+            }
+        }
+    } else {
+        LOG.lock().unwrap().println(&format_args!("No changes detected, skipping recompilation.Extra tip:: Use '-n' flag to always recompile if needed! "), None);
+    }
+
+    if flags.subcommand == "build" {
+        println!("{}", exe);
+        return;
+    }
+
+    if flags.subcommand == "install" {
+        match install::install(&exe, &install_name, &flags.prefix) {
+            Ok(dest) => {
+                println!("Installed {}", dest.display());
+                if let Some(hint) = dest.parent().and_then(install::path_hint) {
+                    println!("{}", hint);
+                }
+            }
+            Err(e) => exit_with(&e),
+        }
+        return;
+    }
+
+    if flags.subcommand == "size" {
+        if let Err(e) = size_report::report(&exe, &build_dir) {
+            exit_with(&e);
+        }
+        return;
+    }
+
+    if !can_run {
+        return report_not_runnable(&exe, &flags.target);
+    }
+
+    if needs_recompile {
         LOG.lock()
             .unwrap()
-            .println(&format_args!("Compilation succeeded"), None);
+            .println(&format_args!("Running binary..."), None);
     }
 
-    unsafe {
+    run_binary(&exe, &runner, &flags);
+}
+
+/// Compiles `src` into `exe`, with `pch_args` for a precompiled prelude; on
+/// failure, returns the compiler's output.
+fn build(
+    compiler: &str,
+    exe: &str,
+    src: &str,
+    settings: &build_env::BuildSettings,
+    pch_args: &[String],
+) -> Result<(), String> {
+    LOG.lock().unwrap().println(
+        &format_args!(
+            "Using compiler: {} ({} family)",
+            compiler,
+            compiler_registry::family_of(compiler).as_str()
+        ),
+        None,
+    );
+
+    let wrapper = settings.wrapper.as_ref().map(|w| w.value.as_str());
+    // cache statistics cost two extra processes, so they are only read for -v
+    let stats_before = wrapper
+        .filter(|_| LOG.lock().unwrap().is_verbose())
+        .and_then(cache_wrapper::stats);
+
+    let (success, output) = compile_helpers::compile(
+        compiler,
+        exe,
+        src,
+        &settings.compile_flags_string(),
+        pch_args,
+        &settings.link_flags_string(),
+        wrapper,
+    );
+    if !success {
         LOG.lock()
             .unwrap()
-            .println(&format_args!("Running binary..."), None);
+            .println(&format_args!("Compilation failed"), None);
+        return Err(output);
     }
 
-    run_binary(&exe, &flags.run_args, &flags);
+    LOG.lock()
+        .unwrap()
+        .println(&format_args!("Compilation succeeded"), None);
+
+    if let (Some(wrapper), Some((hits_before, _))) = (wrapper, stats_before)
+        && let Some((hits, misses)) = cache_wrapper::stats(wrapper)
+    {
+        let outcome = if hits > hits_before { "hit" } else { "miss" };
+        LOG.lock().unwrap().detail(&format_args!(
+            "{}: cache {} ({} hits, {} misses in total)",
+            wrapper, outcome, hits, misses
+        ));
+    }
+    Ok(())
+}
+
+fn exit_with(message: &str) -> ! {
+    LOG.lock()
+        .unwrap()
+        .println(&format_args!("{}", message), None);
+    process::exit(1);
+}
+
+fn report_not_runnable(exe: &str, triple: &str) {
+    println!("Built {} for {}.", exe, triple);
+    println!(
+        "No runner for this target was found (qemu-user for foreign Linux binaries, wine for Windows), so it was not run."
+    );
+}
+
+/// With `-v`, shows where the compiler and each group of flags came from.
+fn report_settings(settings: &build_env::BuildSettings, compiler: &str) {
+    let mut log = LOG.lock().unwrap();
+    match &settings.compiler {
+        Some(c) => log.detail(&format_args!("Compiler: {} (from {})", compiler, c.source)),
+        None => log.detail(&format_args!("Compiler: {} (auto-detected)", compiler)),
+    }
+    if let Some(wrapper) = &settings.wrapper {
+        log.detail(&format_args!(
+            "Cache wrapper: {} (from {})",
+            wrapper.value, wrapper.source
+        ));
+    }
+    for flags in &settings.compile_flags {
+        log.detail(&format_args!(
+            "Compile flags: {} (from {})",
+            flags.value, flags.source
+        ));
+    }
+    for flags in &settings.link_flags {
+        log.detail(&format_args!(
+            "Link flags: {} (from {})",
+            flags.value, flags.source
+        ));
+    }
 }
 
 // --- Flag Parsing ---
@@ -183,15 +592,41 @@ fn flag_alias(arg: &str) -> &str {
         "--run-args" => "-r",
         "--new-terminal" => "-ntw",
         "--doctor" => "-check",
+        "--refresh" => "-refresh",
+        "--target" => "-target",
+        "--emit" => "-emit",
+        "--compare" => "-compare",
+        "--pager" => "-pager",
+        "--function" => "-function",
+        "--kind" => "-kind",
+        "--prefix" => "-prefix",
+        "--cache-wrapper" => "-cache-wrapper",
+        "--no-pch" => "-no-pch",
+        "--no-auto-link" => "-no-auto-link",
+        "--lib" => "-lib",
+        "--driver" => "-driver",
+        "--include" => "-I",
+        "--define" => "-D",
+        "--lib-dir" => "-lib-dir",
+        "--link" => "-link",
+        "--warnings" => "-warnings",
+        "--werror" => "-werror",
+        "--threads" => "-threads",
+        "--openmp" => "-openmp",
+        "--static" => "-static",
+        "--lto" => "-lto",
+        "--debug-info" => "-debug-info",
+        "--compilers" => "-compilers",
+        "--std" => "-std",
+        "--opt" => "-opt",
+        "--input" => "-input",
         other => other,
     }
 }
 
 fn parse_flags() -> (Flags, Vec<String>) {
-    let args: Vec<String> = env::args()
-        .skip(1)
-        .map(|a| flag_alias(&a).to_string())
-        .collect();
+    let raw: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<String> = raw.iter().map(|a| flag_alias(a).to_string()).collect();
     let mut flags = Flags::default();
     let mut non_flags = Vec::new();
     let mut i = 0;
@@ -208,11 +643,82 @@ fn parse_flags() -> (Flags, Vec<String>) {
             "-r" => flags.run_args = args.get(i + 1).cloned().unwrap_or_default(),
             "-ntw" => flags.run_in_new_terminal = true,
             "-check" => flags.check_only = true,
+            "-refresh" => flags.refresh = true,
+            "-target" => flags.target = args.get(i + 1).cloned().unwrap_or_default(),
+            "-emit" => flags.emit = args.get(i + 1).cloned().unwrap_or_default(),
+            "-compare" => {
+                flags.compare = args.iter().skip(i + 1).take(2).cloned().collect();
+                // a source file or a crun flag here means a flag set was left out
+                let misplaced = raw.iter().skip(i + 1).take(2).find(|v| {
+                    Path::new(v).exists()
+                        || backends::for_source(v).is_some()
+                        || (v.starts_with("--") && flag_alias(v) != v.as_str())
+                });
+                if flags.compare.len() < 2 || misplaced.is_some() {
+                    println!(
+                        "--compare takes two flag sets before the source, e.g. --compare -O0 -O2 main.c{}",
+                        misplaced.map_or(String::new(), |v| format!(" ('{}' is not a flag set)", v))
+                    );
+                    process::exit(1);
+                }
+            }
+            "-pager" => flags.pager = true,
+            "-no-pch" => flags.no_pch = true,
+            "-no-auto-link" => flags.no_auto_link = true,
+            "-function" => flags.remark_function = args.get(i + 1).cloned().unwrap_or_default(),
+            "-kind" => flags.remark_kind = args.get(i + 1).cloned().unwrap_or_default(),
+            "-prefix" => flags.prefix = args.get(i + 1).cloned().unwrap_or_default(),
+            "-cache-wrapper" => flags.cache_wrapper = args.get(i + 1).cloned().unwrap_or_default(),
+            "-lib" => flags.lib = args.get(i + 1).cloned().unwrap_or_default(),
+            "-driver" => flags.driver = args.get(i + 1).cloned().unwrap_or_default(),
+            "-compilers" => flags.matrix_compilers = args.get(i + 1).cloned().unwrap_or_default(),
+            "-std" => flags.matrix_stds = args.get(i + 1).cloned().unwrap_or_default(),
+            "-opt" => flags.matrix_opts = args.get(i + 1).cloned().unwrap_or_default(),
+            "-input" => flags.input = args.get(i + 1).cloned().unwrap_or_default(),
+            "-warnings" => {
+                let level = args.get(i + 1).cloned().unwrap_or_default();
+                let Some(level) = toggles::Warnings::parse(&level) else {
+                    println!(
+                        "--warnings expects none, default, all, extra or pedantic (got '{}')",
+                        level
+                    );
+                    process::exit(1);
+                };
+                flags.toggles.warnings = Some(level);
+            }
+            "-werror" => flags.toggles.werror = true,
+            "-threads" => flags.toggles.threads = true,
+            "-openmp" => flags.toggles.openmp = true,
+            "-static" => flags.toggles.static_link = true,
+            "-lto" => flags.toggles.lto = true,
+            "-debug-info" => flags.toggles.debug_info = true,
+            "-I" | "-D" | "-lib-dir" | "-link" => {
+                let kind = match args[i].as_str() {
+                    "-I" => build_env::OptionKind::Include,
+                    "-D" => build_env::OptionKind::Define,
+                    "-lib-dir" => build_env::OptionKind::LibDir,
+                    _ => build_env::OptionKind::Link,
+                };
+                let Some(value) = args.get(i + 1) else {
+                    println!("{} requires an argument", args[i]);
+                    process::exit(1);
+                };
+                flags.options.push((kind, value.clone()));
+            }
+            // the attached forms compilers accept: -Iinclude, -DDEBUG=1
+            s if s.len() > 2 && (s.starts_with("-I") || s.starts_with("-D")) => {
+                let kind = if s.starts_with("-I") {
+                    build_env::OptionKind::Include
+                } else {
+                    build_env::OptionKind::Define
+                };
+                flags.options.push((kind, s[2..].to_string()));
+            }
             "-list-for" => {
                 flags.list_only = true;
                 flags.list_for = args.get(i + 1).cloned().unwrap_or_default();
                 if flags.list_for.is_empty() {
-                    println!("-list-for requires an argument (c or cpp or all)");
+                    println!("-list-for requires a language (c, cpp, fortran, rust, ...) or all");
                     println!("Use -list-all to list all compilers");
                     process::exit(1);
                 }
@@ -223,6 +729,12 @@ fn parse_flags() -> (Flags, Vec<String>) {
                 flags.list_for = "all".to_string();
             }
 
+            "build" | "install" | "size" | "remarks" | "check" | "matrix"
+                if non_flags.is_empty() && flags.subcommand.is_empty() =>
+            {
+                flags.subcommand = args[i].clone();
+            }
+
             "init" => {
                 flags.init_only = true;
                 flags.init_filename = args.get(i + 1).cloned().unwrap_or("main.c".to_string());
@@ -249,13 +761,13 @@ fn parse_flags() -> (Flags, Vec<String>) {
             }
             other => non_flags.push(other.to_string()),
         }
-        i += if matches!(
-            args[i].as_str(),
-            "-c" | "-e" | "-o" | "-d" | "-r" | "-list-for" | "init"
-        ) {
-            2
-        } else {
-            1
+        i += match args[i].as_str() {
+            "-c" | "-e" | "-o" | "-d" | "-r" | "-target" | "-emit" | "-function" | "-kind"
+            | "-prefix" | "-cache-wrapper" | "-lib" | "-driver" | "-I" | "-D" | "-lib-dir"
+            | "-link" | "-warnings" | "-compilers" | "-std" | "-opt" | "-input" | "-list-for"
+            | "init" => 2,
+            "-compare" => 3,
+            _ => 1,
         };
     }
     (flags, non_flags)
@@ -267,6 +779,22 @@ fn show_help() {
     println!("\nFlags:");
 
     println!("  init <filename>      Initialize a new C/C++ file with a template code");
+    println!("  check <filename>     Only check syntax and types of a source or header");
+    println!("  build <filename>     Compile if needed and print the binary path without running");
+    println!("  install <filename>   Build with release flags and copy into <prefix>/bin");
+    println!("  --prefix <dir>       Where install puts the binary (default ~/.local)");
+    println!(
+        "  build --lib static|shared <files>  Build a library; --driver <file> links and runs a test"
+    );
+    println!(
+        "  remarks <filename>   Show optimization remarks inline (--function f, --kind missed)"
+    );
+    println!(
+        "  size <filename>      Build and report section sizes, largest symbols and libraries"
+    );
+    println!(
+        "  matrix <filename>    Build and run with many compilers (--compilers, --std, --opt, --input)"
+    );
 
     // general releted
     println!("  -v, --verbose        Verbose mode");
@@ -274,8 +802,31 @@ fn show_help() {
     println!("  -h, --help           Show help");
 
     // compiler releted
-    println!("  -c, --compiler <c>   Choose compiler");
+    println!(
+        "  -c, --compiler <c>   Choose compiler, optionally with a version (gcc>=12, clang@17)"
+    );
     println!("  -e, --extra <flags>  Extra compiler flags");
+    println!("  -I, --include <dir>  Add an include directory (translated for each compiler)");
+    println!("  -D, --define <m[=v]> Define a preprocessor macro");
+    println!("  --lib-dir <dir>      Add a library search directory");
+    println!("  --link <lib>         Link a library (m, pthread, or a path to a library file)");
+    println!("  --warnings <level>   none, default, all, extra or pedantic");
+    println!("  --werror             Treat warnings as errors");
+    println!("  --threads, --openmp  Build with thread or OpenMP support");
+    println!("  --static, --lto      Link statically / with link-time optimization");
+    println!("  --debug-info         Include debug information");
+    println!(
+        "  --cache-wrapper <w>  Compile through ccache or sccache (auto-detected by default), or none"
+    );
+    println!("  --no-pch             Don't precompile heavy leading includes");
+    println!("  --no-auto-link       Don't link libraries for included headers (math.h -> -lm)");
+    println!(
+        "  --target <triple>    Cross-compile (e.g. aarch64-linux-gnu); runs via qemu or wine"
+    );
+
+    println!("  --emit <kind>        Write pp, asm, ir or obj to .crun/ instead of running");
+    println!("  --compare <a> <b>    With --emit, diff the output of two flag sets");
+    println!("  --pager              Show --emit output in $PAGER");
 
     // output releted
     println!("  -o, --output <name>  Output binary name");
@@ -288,11 +839,14 @@ fn show_help() {
 
     // checkup releted
     println!("  -check, --doctor     Only check for any problem in your machine");
-    println!("  -list-for <c/cpp/all> List available compilers for C or C++ or all");
+    println!("  -list-for <lang|all> List available compilers for a language (c, cpp, rust, ...)");
     println!("  -list-all            List all available compilers");
+    println!("  --refresh            Re-probe compilers instead of using the cached results");
     println!("\nExample:");
     println!("  crun init my_program.c");
     println!("  crun init myprogram             // creates myprogram.c by default");
 
+    println!("  crun -c \"gcc>=12,clang>=15\" my_program.c");
     println!("  crun -v -e \"-Wall -O2\" -r \"arg1 arg2\" my_program.c");
+    println!("  crun matrix main.c --compilers all --std c11,c17 --opt O0,O2");
 }
@@ -0,0 +1,425 @@
+use crate::auto_link;
+use crate::build_env::{BuildOption, Setting};
+use crate::capabilities::{self, cached_capabilities};
+use crate::command_exists::command_exists;
+use crate::compile_helpers::{self, compile_args};
+use crate::compiler_registry::{self, Family, Lang};
+use crate::probe_cache::parallel_map;
+use crate::toggles::{self, Toggles};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Programs stuck in a loop under one compiler should not hang the whole matrix.
+const RUN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What `crun matrix` was asked to build: comma-separated lists from the
+/// command line, an empty list meaning "the compiler's default".
+pub struct MatrixRequest<'a> {
+    /// `all`, or compiler names/selectors.
+    pub compilers: &'a str,
+    pub stds: &'a [String],
+    pub opts: &'a [String],
+    /// File fed to every run on stdin; empty for no input.
+    pub input: &'a str,
+    pub run_args: &'a str,
+}
+
+/// The flags every build shares, before they are translated for a compiler.
+pub struct SharedFlags<'a> {
+    pub compile: &'a str,
+    pub link: &'a str,
+    pub options: &'a [BuildOption],
+    pub toggles: &'a Toggles,
+    pub auto_link: bool,
+}
+
+pub fn split_list(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Installed compilers for `lang`, the same ones `-list-for` shows.
+fn installed_compilers(lang: Lang) -> Vec<String> {
+    compiler_registry::discover_installations()
+        .into_iter()
+        .filter(|i| compiler_registry::lookup(i.core).is_some_and(|d| d.supports(lang)))
+        .map(|i| i.selector())
+        .filter(|selector| compiler_registry::has_front_end(selector, lang))
+        .collect()
+}
+
+fn opt_flag(family: Family, level: &str) -> Option<String> {
+    let level = level.trim_start_matches(['-', '/']);
+    match family {
+        Family::Msvc => match level {
+            "O0" | "Od" => Some("/Od".to_string()),
+            "O1" | "Os" => Some("/O1".to_string()),
+            "O2" | "O3" => Some("/O2".to_string()),
+            _ => None,
+        },
+        Family::Go => None,
+        _ => Some(format!("-{}", level)),
+    }
+}
+
+enum BuildStatus {
+    Built,
+    Failed,
+    /// The compiler cannot build this combination at all (missing, or no such standard).
+    Skipped(String),
+}
+
+struct Cell {
+    compiler: String,
+    std: String,
+    opt: String,
+    exe: String,
+    status: BuildStatus,
+    warnings: usize,
+}
+
+struct RunResult {
+    exit: String,
+    time: Duration,
+    stdout: Option<String>,
+}
+
+fn count_warnings(output: &str) -> usize {
+    output
+        .lines()
+        .filter(|line| line.contains("warning:") || line.contains(": warning C"))
+        .count()
+}
+
+/// Flags for one cell, or why the compiler cannot build it.
+fn cell_flags(
+    compiler: &str,
+    lang: Lang,
+    src: &str,
+    std: &str,
+    opt: &str,
+    shared: &SharedFlags,
+) -> Result<(String, String), String> {
+    let family = compiler_registry::family_of(compiler);
+    let mut compile = vec![shared.compile.to_string()];
+    if !std.is_empty() {
+        // a standard the cache lacks is rechecked: the compiler's headers may have been updated
+        if let Some(caps) = cached_capabilities(compiler, lang)
+            && !caps.standards().contains(&std)
+            && !capabilities::recheck(compiler, lang).standards().contains(&std)
+        {
+            return Err(format!("no {}", std));
+        }
+        compile.push(capabilities::std_flag(family, std).ok_or(format!("no {}", std))?);
+    }
+    if !opt.is_empty() {
+        compile.push(opt_flag(family, opt).ok_or(format!("no {}", opt))?);
+    }
+    let (option_compile, option_link) = compile_helpers::option_flags(compiler, shared.options)?;
+    let (toggle_compile, toggle_link) = toggles::toggle_flags(compiler, lang, shared.toggles)?;
+    let join = |base: Vec<String>, more: Vec<Setting>| {
+        base.into_iter()
+            .chain(more.into_iter().map(|s| s.value))
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    compile.push(join(Vec::new(), option_compile));
+    let compile = join(compile, toggle_compile);
+    let link = join(vec![shared.link.to_string()], option_link);
+    let link = join(vec![link], toggle_link);
+    if !shared.auto_link {
+        return Ok((compile, link));
+    }
+    let existing = format!("{} {}", compile, link);
+    let (auto_compile, auto_link) = auto_link::link_flags(compiler, lang, src, &existing);
+    Ok((join(vec![compile], auto_compile), join(vec![link], auto_link)))
+}
+
+fn build_cell(mut cell: Cell, lang: Lang, src: &str, shared: &SharedFlags) -> Cell {
+    if !command_exists(compiler_registry::command_for(&cell.compiler)) {
+        cell.status = BuildStatus::Skipped("not found".to_string());
+        return cell;
+    }
+    let (compile, link) = match cell_flags(&cell.compiler, lang, src, &cell.std, &cell.opt, shared) {
+        Ok(flags) => flags,
+        Err(e) => {
+            cell.status = BuildStatus::Skipped(e);
+            return cell;
+        }
+    };
+    let output = Command::new(compiler_registry::command_for(&cell.compiler))
+        .args(compile_args(&cell.compiler, &cell.exe, src, &compile, &link))
+        .stdin(Stdio::null())
+        .output();
+    match output {
+        Ok(output) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            cell.warnings = count_warnings(&text);
+            cell.status = if output.status.success() {
+                BuildStatus::Built
+            } else {
+                BuildStatus::Failed
+            };
+            // keep the diagnostics next to the binary for a closer look
+            let _ = fs::write(Path::new(&cell.exe).with_extension("log"), text);
+        }
+        Err(_) => cell.status = BuildStatus::Failed,
+    }
+    cell
+}
+
+/// Runs `exe` with `input` on stdin, killing it after [`RUN_TIMEOUT`].
+fn run_cell(exe: &str, run_args: &str, input: Option<&[u8]>) -> RunResult {
+    let start = Instant::now();
+    let child = Command::new(exe)
+        .args(run_args.split_whitespace())
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            return RunResult {
+                exit: format!("error: {}", e),
+                time: start.elapsed(),
+                stdout: None,
+            };
+        }
+    };
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        let input = input.to_vec();
+        // written from a thread so a program that does not read its input cannot block us
+        std::thread::spawn(move || {
+            use std::io::Write;
+            let _ = stdin.write_all(&input);
+        });
+    }
+    let reader = child.stdout.take().map(|mut out| {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = out.read_to_end(&mut buf);
+            buf
+        })
+    });
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if start.elapsed() > RUN_TIMEOUT => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(1)),
+            Err(_) => break None,
+        }
+    };
+    let time = start.elapsed();
+    let stdout = reader
+        .and_then(|r| r.join().ok())
+        .map(|buf| String::from_utf8_lossy(&buf).into_owned());
+    let exit = match status {
+        Some(status) => match status.code() {
+            Some(code) => code.to_string(),
+            None => "signal".to_string(),
+        },
+        None => "timeout".to_string(),
+    };
+    RunResult { exit, time, stdout }
+}
+
+/// The output most runs agree on; ties go to the one seen first.
+fn majority(outputs: &[Option<String>]) -> Option<&str> {
+    let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (idx, output) in outputs.iter().enumerate() {
+        if let Some(output) = output {
+            counts.entry(output.as_str()).or_insert((0, idx)).0 += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .max_by(|(_, (a, a_idx)), (_, (b, b_idx))| a.cmp(b).then(b_idx.cmp(a_idx)))
+        .map(|(output, _)| output)
+}
+
+fn file_part(text: &str) -> String {
+    let name = Path::new(text)
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    name.replace(|c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '+', "_")
+}
+
+/// Builds `src` with every combination of compiler, standard and
+/// optimization level, runs each binary with the same input and prints a
+/// table comparing them.
+pub fn run(
+    src: &str,
+    lang: Lang,
+    build_dir: &str,
+    request: &MatrixRequest,
+    shared: &SharedFlags,
+) -> Result<(), String> {
+    let compilers = match request.compilers {
+        "" | "all" => installed_compilers(lang),
+        list => split_list(list),
+    };
+    if compilers.is_empty() {
+        return Err(format!(
+            "No compilers found for {} (looked for {})",
+            src,
+            compiler_registry::candidate_names(lang).join(", ")
+        ));
+    }
+    let input = if request.input.is_empty() {
+        None
+    } else {
+        Some(fs::read(request.input).map_err(|e| format!("Cannot read {}: {}", request.input, e))?)
+    };
+
+    let dir = Path::new(build_dir).join("matrix");
+    fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    let or_default = |list: &[String]| {
+        if list.is_empty() {
+            vec![String::new()]
+        } else {
+            list.to_vec()
+        }
+    };
+    let mut cells = Vec::new();
+    for compiler in &compilers {
+        for std in or_default(request.stds) {
+            for opt in or_default(request.opts) {
+                let name = format!(
+                    "{}-{}-{}-{}",
+                    file_part(src),
+                    file_part(compiler),
+                    if std.is_empty() { "default" } else { &std },
+                    if opt.is_empty() { "default" } else { &opt }
+                );
+                let exe = dir.join(name.replace('.', "_"));
+                cells.push(Cell {
+                    compiler: compiler.clone(),
+                    std: std.clone(),
+                    opt: opt.clone(),
+                    exe: format!("{}{}", exe.display(), std::env::consts::EXE_SUFFIX),
+                    status: BuildStatus::Failed,
+                    warnings: 0,
+                });
+            }
+        }
+    }
+    println!("Building {} combinations...", cells.len());
+    let cells = parallel_map(cells, |cell| build_cell(cell, lang, src, shared));
+
+    // one at a time, so the timings are not skewed by the other runs
+    let runs: Vec<Option<RunResult>> = cells
+        .iter()
+        .map(|cell| {
+            matches!(cell.status, BuildStatus::Built)
+                .then(|| run_cell(&cell.exe, request.run_args, input.as_deref()))
+        })
+        .collect();
+    let outputs: Vec<Option<String>> = runs
+        .iter()
+        .map(|r| r.as_ref().and_then(|r| r.stdout.clone()))
+        .collect();
+    let expected = majority(&outputs);
+
+    let rows: Vec<[String; 8]> = cells
+        .iter()
+        .zip(&runs)
+        .map(|(cell, run)| {
+            let build = match &cell.status {
+                BuildStatus::Built => "ok".to_string(),
+                BuildStatus::Failed => "FAILED".to_string(),
+                BuildStatus::Skipped(why) => format!("skipped ({})", why),
+            };
+            let warnings = match cell.status {
+                BuildStatus::Skipped(_) => "-".to_string(),
+                _ => cell.warnings.to_string(),
+            };
+            let (exit, time, same) = match run {
+                Some(run) => (
+                    run.exit.clone(),
+                    format!("{:.1} ms", run.time.as_secs_f64() * 1000.0),
+                    if run.stdout.as_deref() == expected { "yes" } else { "NO" }.to_string(),
+                ),
+                None => ("-".to_string(), "-".to_string(), "-".to_string()),
+            };
+            [
+                cell.compiler.clone(),
+                or_default_name(&cell.std),
+                or_default_name(&cell.opt),
+                build,
+                warnings,
+                exit,
+                time,
+                same,
+            ]
+        })
+        .collect();
+
+    let header = [
+        "COMPILER", "STD", "OPT", "BUILD", "WARNINGS", "EXIT", "TIME", "SAME OUTPUT",
+    ];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let print_row = |cells: &[&str]| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(&header);
+    for row in &rows {
+        print_row(&row.each_ref().map(String::as_str));
+    }
+
+    for ((cell, run), row) in cells.iter().zip(&runs).zip(&rows) {
+        if let Some(stdout) = run.as_ref().and_then(|r| r.stdout.as_ref())
+            && row[7] == "NO"
+        {
+            let path = Path::new(&cell.exe).with_extension("out");
+            let _ = fs::write(&path, stdout);
+        }
+    }
+    let differing = rows.iter().filter(|row| row[7] == "NO").count();
+    if differing > 0 {
+        println!(
+            "\n{} of {} runs printed something else than the majority; their output is in {}/*.out",
+            differing,
+            runs.iter().flatten().count(),
+            dir.display()
+        );
+    }
+    Ok(())
+}
+
+fn or_default_name(value: &str) -> String {
+    if value.is_empty() {
+        "default".to_string()
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,168 @@
+use crate::compiler_registry::{self, Family, Lang};
+use crate::compiler_version::cached_compiler_version;
+use crate::fs_and_path_helpers::user_crun_dir;
+use crate::{LOG, compile_helpers};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+/// Headers expensive enough that precompiling them pays off on their own.
+const HEAVY_HEADERS: &[&str] = &["bits/stdc++.h", "bits/extc++.h"];
+
+/// A prelude of this many standard headers is worth precompiling even without a heavy one.
+const MIN_PRELUDE_HEADERS: usize = 4;
+
+/// The `#include <...>` lines a source starts with, up to the first line that
+/// is anything else (comments and blank lines are skipped).
+fn leading_includes(source: &str) -> Vec<String> {
+    let mut includes = Vec::new();
+    let mut in_block_comment = false;
+    for line in source.lines() {
+        let line = line.trim();
+        if in_block_comment {
+            in_block_comment = !line.contains("*/");
+            continue;
+        }
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        if line.starts_with("/*") {
+            in_block_comment = !line.contains("*/");
+            continue;
+        }
+        let Some(rest) = line.strip_prefix('#') else {
+            break;
+        };
+        let Some(header) = rest.trim_start().strip_prefix("include") else {
+            break;
+        };
+        let header = header.trim();
+        // quoted includes are project files that change; only system headers are stable
+        if !header.starts_with('<') {
+            break;
+        }
+        includes.push(header.to_string());
+    }
+    includes
+}
+
+/// FNV-1a, stable across builds so cache directory names do not change with the toolchain.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn pch_extension(family: Family) -> Option<&'static str> {
+    match family {
+        Family::Gcc => Some("gch"),
+        Family::Clang => Some("pch"),
+        _ => None,
+    }
+}
+
+/// Builds `<header>.<ext>` from `header` with the same flags the source will
+/// be compiled with; a PCH is only usable with matching flags.
+fn build_pch(compiler: &str, header: &Path, out: &Path, flags: &str) -> bool {
+    let def = compiler_registry::definition_for(compiler);
+    let tmp = out.with_extension(format!("tmp{}", std::process::id()));
+    let ok = Command::new(compiler_registry::command_for(compiler))
+        .args(&def.prefix_args)
+        .args(&def.default_args)
+        .args(flags.split_whitespace())
+        .args(["-x", "c++-header"])
+        .arg(header)
+        .arg("-o")
+        .arg(&tmp)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+    // rename so a concurrent crun never picks up a half-written PCH
+    let ok = ok && fs::rename(&tmp, out).is_ok();
+    if !ok {
+        let _ = fs::remove_file(&tmp);
+    }
+    ok
+}
+
+/// Arguments that make `compiler` use a precompiled copy of the includes `source`
+/// starts with, building it on first use. Returns `None` when the source has
+/// no heavy prelude, the compiler cannot precompile headers, or building fails;
+/// the source then compiles as usual.
+///
+/// PCHs live in `~/.crun/pch/<key>/`, keyed on the compiler binary, its version,
+/// the flags and the prelude, so every file with the same prelude shares one.
+pub fn prelude_args(compiler: &str, source: &str, flags: &str) -> Option<Vec<String>> {
+    if compile_helpers::source_lang(source) != Lang::Cpp {
+        return None;
+    }
+    let ext = pch_extension(compiler_registry::family_of(compiler))?;
+    let includes = leading_includes(&fs::read_to_string(source).ok()?);
+    let heavy = includes
+        .iter()
+        .any(|h| HEAVY_HEADERS.contains(&h.trim_matches(['<', '>'])));
+    if !heavy && includes.len() < MIN_PRELUDE_HEADERS {
+        return None;
+    }
+
+    let prelude: String = includes
+        .iter()
+        .map(|h| format!("#include {}\n", h))
+        .collect();
+    let version = cached_compiler_version(compiler)
+        .map(|v| v.to_string())
+        .unwrap_or_default();
+    let key = fnv1a(&format!(
+        "{}\n{}\n{}\n{}",
+        compiler_registry::resolve_path(compiler),
+        version,
+        flags.split_whitespace().collect::<Vec<_>>().join(" "),
+        prelude
+    ));
+
+    let dir: PathBuf = user_crun_dir()?.join("pch").join(format!("{:016x}", key));
+    let header = dir.join("prelude.hpp");
+    let pch = dir.join(format!("prelude.hpp.{}", ext));
+
+    if pch.exists() {
+        LOG.lock().unwrap().detail(&format_args!(
+            "Precompiled header: reusing {}",
+            pch.display()
+        ));
+    } else {
+        fs::create_dir_all(&dir).ok()?;
+        fs::write(&header, &prelude).ok()?;
+        LOG.lock().unwrap().println(
+            &format_args!(
+                "Precompiling {} (once per compiler and flags)...",
+                includes.join(" ")
+            ),
+            None,
+        );
+        let started = Instant::now();
+        if !build_pch(compiler, &header, &pch, flags) {
+            LOG.lock().unwrap().detail(&format_args!(
+                "Precompiled header: building {} failed, compiling without it",
+                pch.display()
+            ));
+            return None;
+        }
+        LOG.lock().unwrap().detail(&format_args!(
+            "Precompiled header: built {} in {:.1}s",
+            pch.display(),
+            started.elapsed().as_secs_f64()
+        ));
+    }
+
+    // gcc picks up prelude.hpp.gch next to the included header by itself;
+    // kept as separate arguments since the home directory may contain spaces
+    let (flag, path) = match ext {
+        "pch" => ("-include-pch", pch),
+        _ => ("-include", header),
+    };
+    Some(vec![flag.to_string(), path.to_string_lossy().to_string()])
+}
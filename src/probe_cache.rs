@@ -0,0 +1,187 @@
+use crate::compiler_registry;
+use crate::fs_and_path_helpers::user_crun_dir;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::UNIX_EPOCH;
+
+/// Size and modification time of a compiler binary. A changed fingerprint
+/// (upgrade, reinstall) invalidates everything cached for that path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Fingerprint {
+    mtime: u64,
+    size: u64,
+}
+
+fn fingerprint(path: &Path) -> Option<Fingerprint> {
+    let meta = fs::metadata(path).ok()?;
+    let mtime = meta
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some(Fingerprint {
+        mtime,
+        size: meta.len(),
+    })
+}
+
+#[derive(Default)]
+struct ProbeCache {
+    loaded: bool,
+    // (compiler path, kind) -> (fingerprint, value)
+    entries: HashMap<(String, String), (Fingerprint, String)>,
+}
+
+static CACHE: LazyLock<Mutex<ProbeCache>> = LazyLock::new(|| Mutex::new(ProbeCache::default()));
+static REFRESH: AtomicBool = AtomicBool::new(false);
+
+/// Makes every lookup miss, so probes run again and overwrite the cache (`--refresh`).
+pub fn set_refresh(refresh: bool) {
+    REFRESH.store(refresh, Ordering::Relaxed);
+}
+
+fn cache_file() -> Option<PathBuf> {
+    user_crun_dir().map(|d| d.join("cache").join("probes"))
+}
+
+// One line per entry: "<path>\t<mtime>\t<size>\t<kind>\t<value>"
+fn load(cache: &mut ProbeCache) {
+    if cache.loaded {
+        return;
+    }
+    cache.loaded = true;
+    let Some(file) = cache_file() else {
+        return;
+    };
+    for line in fs::read_to_string(file).unwrap_or_default().lines() {
+        let fields: Vec<&str> = line.splitn(5, '\t').collect();
+        let [path, mtime, size, kind, value] = fields[..] else {
+            continue;
+        };
+        let (Ok(mtime), Ok(size)) = (mtime.parse(), size.parse()) else {
+            continue;
+        };
+        cache.entries.insert(
+            (path.to_string(), kind.to_string()),
+            (Fingerprint { mtime, size }, value.to_string()),
+        );
+    }
+}
+
+fn save(cache: &ProbeCache) {
+    let Some(file) = cache_file() else {
+        return;
+    };
+    let mut lines: Vec<String> = cache
+        .entries
+        .iter()
+        .map(|((path, kind), (fp, value))| {
+            format!("{}\t{}\t{}\t{}\t{}\n", path, fp.mtime, fp.size, kind, value)
+        })
+        .collect();
+    lines.sort();
+
+    if let Some(parent) = file.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    // write to a temporary file first so a concurrent crun never reads half a cache
+    let tmp = file.with_extension(format!("tmp{}", std::process::id()));
+    if fs::write(&tmp, lines.concat()).is_ok() {
+        let _ = fs::rename(&tmp, &file);
+    }
+}
+
+fn resolve(compiler: &str) -> Option<PathBuf> {
+    let found = compiler_registry::resolve_path(compiler);
+    (!found.is_empty()).then(|| PathBuf::from(found))
+}
+
+/// The cached `kind` entry for `compiler`, if the binary has not changed since it was recorded.
+pub fn lookup(compiler: &str, kind: &str) -> Option<String> {
+    if REFRESH.load(Ordering::Relaxed) {
+        return None;
+    }
+    let path = resolve(compiler)?;
+    let current = fingerprint(&path)?;
+
+    let mut cache = CACHE.lock().unwrap();
+    load(&mut cache);
+    match cache
+        .entries
+        .get(&(path.to_string_lossy().to_string(), kind.to_string()))
+    {
+        Some((fp, value)) if *fp == current => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Stores a probe result for `compiler` and writes the cache back to disk.
+pub fn record(compiler: &str, kind: &str, value: &str) {
+    let Some(path) = resolve(compiler) else {
+        return;
+    };
+    let Some(fp) = fingerprint(&path) else {
+        return;
+    };
+    // values are single-line by construction; keep the file parseable regardless
+    let value = value.replace(['\n', '\r'], " ");
+
+    let mut cache = CACHE.lock().unwrap();
+    load(&mut cache);
+    cache.entries.insert(
+        (path.to_string_lossy().to_string(), kind.to_string()),
+        (fp, value),
+    );
+    save(&cache);
+}
+
+/// Returns the cached `kind` value for `compiler`, running `probe` and caching
+/// its result on a miss.
+pub fn cached_or_probe(compiler: &str, kind: &str, probe: impl FnOnce() -> String) -> String {
+    if let Some(value) = lookup(compiler, kind) {
+        return value;
+    }
+    let value = probe();
+    record(compiler, kind, &value);
+    value
+}
+
+/// Runs `job` over `items` on at most `available_parallelism` threads (capped at 8),
+/// keeping the results in input order.
+pub fn parallel_map<T, R, F>(items: Vec<T>, job: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(2)
+        .clamp(1, 8)
+        .min(items.len().max(1));
+
+    let queue: Mutex<Vec<(usize, T)>> = Mutex::new(items.into_iter().enumerate().rev().collect());
+    let results: Mutex<Vec<(usize, R)>> = Mutex::new(Vec::new());
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let Some((idx, item)) = queue.lock().unwrap().pop() else {
+                        break;
+                    };
+                    let result = job(item);
+                    results.lock().unwrap().push((idx, result));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, r)| r).collect()
+}
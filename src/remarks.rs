@@ -0,0 +1,296 @@
+use crate::emit;
+use crate::compiler_registry::{self, Family};
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RemarkKind {
+    /// An optimization that was applied (clang `-Rpass`, gcc `optimized:`).
+    Passed,
+    /// One that was attempted but not applied (`-Rpass-missed`, `missed:`).
+    Missed,
+    /// Extra detail explaining a decision (`-Rpass-analysis`, gcc `note:`).
+    Analysis,
+}
+
+impl RemarkKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RemarkKind::Passed => "passed",
+            RemarkKind::Missed => "missed",
+            RemarkKind::Analysis => "analysis",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            RemarkKind::Passed => "\x1b[32m",
+            RemarkKind::Missed => "\x1b[31m",
+            RemarkKind::Analysis => "\x1b[36m",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Remark {
+    line: usize,
+    col: usize,
+    kind: RemarkKind,
+    /// Optimization pass, e.g. `inline` or `loop-vectorize`; empty for gcc.
+    pass: String,
+    message: String,
+}
+
+/// What to keep: remarks inside `function`, and of a kind or pass containing `kind`.
+pub struct RemarkFilter<'a> {
+    pub function: &'a str,
+    pub kind: &'a str,
+}
+
+/// The flags that make the compiler report its optimization decisions.
+fn remark_flags(family: Family) -> Result<&'static str, String> {
+    match family {
+        Family::Clang | Family::Zig => {
+            Ok("-Rpass=.* -Rpass-missed=.* -Rpass-analysis=.* -gline-tables-only")
+        }
+        Family::Gcc => Ok("-fopt-info-all"),
+        family => Err(format!(
+            "Optimization remarks need clang or gcc; {} family compilers cannot produce them",
+            family.as_str()
+        )),
+    }
+}
+
+/// Splits `path:line:col: rest`, allowing a drive letter or other colons in the path.
+fn split_location(text: &str) -> Option<(&str, usize, usize, &str)> {
+    for (idx, _) in text.match_indices(':') {
+        let mut parts = text[idx + 1..].splitn(3, ':');
+        let (Some(line), Some(col), Some(rest)) = (parts.next(), parts.next(), parts.next()) else {
+            continue;
+        };
+        if let (Ok(line), Ok(col)) = (line.parse(), col.parse()) {
+            return Some((&text[..idx], line, col, rest.trim_start()));
+        }
+    }
+    None
+}
+
+/// Parses clang remarks (`remark: ... [-Rpass-missed=inline]`) and gcc
+/// `-fopt-info` lines (`optimized: ...`, `missed: ...`) for `source`.
+fn parse_remarks(output: &str, source: &str) -> Vec<Remark> {
+    let source_name = Path::new(source).file_name();
+    let mut remarks = Vec::new();
+    for line in output.lines() {
+        let Some((file, line_no, col, rest)) = split_location(line) else {
+            continue;
+        };
+        // remarks from headers are not shown next to this file
+        if Path::new(file).file_name() != source_name {
+            continue;
+        }
+
+        let (kind, pass, message) = if let Some(msg) = rest.strip_prefix("remark: ") {
+            let (message, flag) = match msg.rsplit_once(" [-R") {
+                Some((m, f)) => (m, f.trim_end_matches(']')),
+                None => (msg, ""),
+            };
+            let (option, pass) = flag.split_once('=').unwrap_or((flag, ""));
+            let kind = match option {
+                "pass-missed" => RemarkKind::Missed,
+                "pass-analysis" => RemarkKind::Analysis,
+                _ => RemarkKind::Passed,
+            };
+            (kind, pass.to_string(), message.to_string())
+        } else if let Some(msg) = rest.strip_prefix("optimized: ") {
+            (RemarkKind::Passed, String::new(), msg.to_string())
+        } else if let Some(msg) = rest.strip_prefix("missed: ") {
+            (RemarkKind::Missed, String::new(), msg.to_string())
+        } else if let Some(msg) = rest.strip_prefix("note: ") {
+            (RemarkKind::Analysis, String::new(), msg.to_string())
+        } else {
+            continue;
+        };
+
+        remarks.push(Remark {
+            line: line_no,
+            col,
+            kind,
+            pass,
+            message,
+        });
+    }
+    remarks.sort_by_key(|r| (r.line, r.col));
+    remarks.dedup_by(|a, b| a.line == b.line && a.message == b.message);
+    remarks
+}
+
+/// Top-level function bodies as `(name, first line, last line)`, found by
+/// tracking braces. Comments and string literals are skipped; macros that
+/// open or close braces can throw it off, which only affects `--function`.
+fn function_spans(source: &str) -> Vec<(String, usize, usize)> {
+    let mut spans = Vec::new();
+    let mut depth = 0usize;
+    let mut header = String::new();
+    let mut current: Option<(String, usize)> = None;
+    let mut in_block_comment = false;
+
+    for (idx, line) in source.lines().enumerate() {
+        let line_no = idx + 1;
+        let mut chars = line.chars().peekable();
+        let mut in_string: Option<char> = None;
+        while let Some(c) = chars.next() {
+            if in_block_comment {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    in_block_comment = false;
+                }
+                continue;
+            }
+            if let Some(quote) = in_string {
+                if c == '\\' {
+                    chars.next();
+                } else if c == quote {
+                    in_string = None;
+                }
+                continue;
+            }
+            match c {
+                '/' if chars.peek() == Some(&'/') => break,
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    in_block_comment = true;
+                }
+                '"' | '\'' => in_string = Some(c),
+                '#' if depth == 0 && header.trim().is_empty() => break,
+                '{' => {
+                    if depth == 0 {
+                        current = function_name(&header).map(|name| (name, line_no));
+                        header.clear();
+                    }
+                    depth += 1;
+                }
+                '}' => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        if let Some((name, start)) = current.take() {
+                            spans.push((name, start, line_no));
+                        }
+                        header.clear();
+                    }
+                }
+                ';' if depth == 0 => header.clear(),
+                _ if depth == 0 => header.push(c),
+                _ => {}
+            }
+        }
+        if depth == 0 {
+            header.push(' ');
+        }
+    }
+    spans
+}
+
+/// The identifier right before the parameter list of a definition header,
+/// e.g. `sum` in `static int sum(int *a, int n)`. Structs and namespaces have none.
+fn function_name(header: &str) -> Option<String> {
+    let open = header.find('(')?;
+    let name: String = header[..open]
+        .trim_end()
+        .chars()
+        .rev()
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == ':' || *c == '~')
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+fn keep(remark: &Remark, filter: &RemarkFilter, span: Option<(usize, usize)>) -> bool {
+    if let Some((start, end)) = span
+        && !(start..=end).contains(&remark.line)
+    {
+        return false;
+    }
+    let kind = filter.kind.to_ascii_lowercase();
+    kind.is_empty()
+        || remark.kind.as_str() == kind
+        || remark.pass.to_ascii_lowercase().contains(&kind)
+        || remark.message.to_ascii_lowercase().contains(&kind)
+}
+
+/// Compiles `source` with optimization remarks enabled and prints the source
+/// annotated with them. `extra` is used as-is, with `-O2` added when it sets
+/// no optimization level (nothing is optimized at the default `-O0`).
+pub fn show_remarks(
+    compiler: &str,
+    source: &str,
+    exe: &str,
+    extra: &str,
+    filter: &RemarkFilter,
+) -> Result<(), String> {
+    let family = compiler_registry::family_of(compiler);
+    let mut flags = format!("{} {}", extra, remark_flags(family)?);
+    if !extra.split_whitespace().any(|f| f.starts_with("-O")) {
+        flags.push_str(" -O2");
+    }
+
+    // remarks come from the compile step, so nothing is linked
+    let object = format!("{}-remarks.o", exe);
+    let (ok, output) = emit::compile_object_captured(compiler, source, &flags, &object)?;
+    let _ = fs::remove_file(&object);
+    if !ok {
+        print!("{}", output);
+        return Err("Compilation failed".to_string());
+    }
+
+    let text = fs::read_to_string(source).map_err(|e| e.to_string())?;
+    let span = if filter.function.is_empty() {
+        None
+    } else {
+        let spans = function_spans(&text);
+        match spans.iter().find(|(name, _, _)| {
+            name == filter.function || name.rsplit("::").next() == Some(filter.function)
+        }) {
+            Some((_, start, end)) => Some((*start, *end)),
+            None => return Err(format!("No function '{}' in {}", filter.function, source)),
+        }
+    };
+
+    let remarks: Vec<Remark> = parse_remarks(&output, source)
+        .into_iter()
+        .filter(|r| keep(r, filter, span))
+        .collect();
+
+    let (first, last) = span.unwrap_or((1, text.lines().count()));
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        if line_no < first || line_no > last {
+            continue;
+        }
+        println!("{:>5} | {}", line_no, line);
+        for remark in remarks.iter().filter(|r| r.line == line_no) {
+            let label = if remark.pass.is_empty() {
+                remark.kind.as_str().to_string()
+            } else {
+                format!("{} {}", remark.kind.as_str(), remark.pass)
+            };
+            println!(
+                "      | {}{}^ [{}] {}\x1b[0m",
+                remark.kind.color(),
+                " ".repeat(remark.col.saturating_sub(1)),
+                label,
+                remark.message
+            );
+        }
+    }
+
+    let count = |kind| remarks.iter().filter(|r| r.kind == kind).count();
+    println!(
+        "\n{} passed, {} missed, {} analysis remarks",
+        count(RemarkKind::Passed),
+        count(RemarkKind::Missed),
+        count(RemarkKind::Analysis)
+    );
+    Ok(())
+}
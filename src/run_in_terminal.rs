@@ -1,18 +1,10 @@
+#[cfg(windows)]
 use std::env;
+#[cfg(windows)]
 use std::fs;
+#[cfg(windows)]
 use std::path::PathBuf;
-use std::process::{Command, Stdio};
-
-fn run_command(cmd: &str, args: &[&str]) -> bool {
-    Command::new(cmd)
-        .args(args)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
-}
+use std::process::Command;
 
 #[cfg(windows)]
 pub fn launch_in_external_terminal(binary_path: &str, args: &[&str]) -> std::io::Result<()> {
@@ -64,10 +56,85 @@ end tell"#,
     Ok(())
 }
 
+// Universal first
 #[cfg(all(unix, not(target_os = "macos")))]
-pub fn launch_in_external_terminal(binary_path: &str, args: &[&str]) -> std::io::Result<()> {
-    use std::process::Command;
+static PRIMARY_TERMINALS: &[&str] = &["x-terminal-emulator", "xterm"];
+
+// Exhaustive fallback list
+#[cfg(all(unix, not(target_os = "macos")))]
+static FALLBACK_TERMINALS: &[&str] = &[
+    // GNOME & GTK
+    "gnome-terminal",
+    "tilix",
+    "terminator",
+    "mate-terminal",
+    "lxterminal",
+    "xfce4-terminal",
+    "deepin-terminal",
+    "pantheon-terminal",
+    "elementary-terminal",
+    // KDE & Qt
+    "konsole",
+    "yakuake",
+    "qterminal",
+    // Lightweight / minimalist
+    "alacritty",
+    "kitty",
+    "urxvt",
+    "rxvt",
+    "st",
+    "eterm",
+    "sakura",
+    "tilda",
+    "guake",
+    "cool-retro-term",
+    "lxterm",
+    "mlterm",
+    "roxterm",
+    "termite",
+    "xvt",
+    // Wayland-native or experimental
+    "foot",
+    "wezterm",
+    "warp-terminal",
+    // Others / niche
+    "hyper",
+    "terminal",
+    "gnustep-terminal",
+    "terminology",
+    "yeahconsole",
+    "evilvte",
+    "gnome-console",
+    "blackbox-terminal",
+    "wterm",
+    "x3270",
+    "aterm",
+    "zterm",
+    "finalterm",
+];
+
+/// The terminal emulator `-ntw` would use, if one is installed.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn find_terminal() -> Option<&'static str> {
+    PRIMARY_TERMINALS
+        .iter()
+        .chain(FALLBACK_TERMINALS.iter())
+        .find(|term| crate::command_exists::command_exists(term))
+        .copied()
+}
 
+#[cfg(target_os = "macos")]
+pub fn find_terminal() -> Option<&'static str> {
+    crate::command_exists::command_exists("osascript").then_some("Terminal (osascript)")
+}
+
+#[cfg(windows)]
+pub fn find_terminal() -> Option<&'static str> {
+    Some("cmd.exe")
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn launch_in_external_terminal(binary_path: &str, args: &[&str]) -> std::io::Result<()> {
     let quoted_binary = format!("\"{}\"", binary_path);
     let quoted_args: Vec<String> = args.iter().map(|a| format!("\"{}\"", a)).collect();
     let arg_line = quoted_args.join(" ");
@@ -76,70 +143,11 @@ pub fn launch_in_external_terminal(binary_path: &str, args: &[&str]) -> std::io:
         quoted_binary, arg_line
     );
 
-    // Universal first
-    let primary_terminals = ["x-terminal-emulator", "xterm"];
-
-    // Exhaustive fallback list
-    let fallback_terminals = [
-        // GNOME & GTK
-        "gnome-terminal",
-        "tilix",
-        "terminator",
-        "mate-terminal",
-        "lxterminal",
-        "xfce4-terminal",
-        "deepin-terminal",
-        "pantheon-terminal",
-        "elementary-terminal",
-        // KDE & Qt
-        "konsole",
-        "yakuake",
-        "qterminal",
-        // Lightweight / minimalist
-        "alacritty",
-        "kitty",
-        "urxvt",
-        "rxvt",
-        "st",
-        "eterm",
-        "sakura",
-        "tilda",
-        "guake",
-        "cool-retro-term",
-        "lxterm",
-        "mlterm",
-        "roxterm",
-        "termite",
-        "xvt",
-        // Wayland-native or experimental
-        "foot",
-        "wezterm",
-        "warp-terminal",
-        // Others / niche
-        "hyper",
-        "terminal",
-        "gnustep-terminal",
-        "terminology",
-        "yeahconsole",
-        "evilvte",
-        "gnome-console",
-        "blackbox-terminal",
-        "wterm",
-        "x3270",
-        "aterm",
-        "zterm",
-        "finalterm",
-    ];
-
-    let terminal = primary_terminals
-        .iter()
-        .chain(fallback_terminals.iter())
-        .find(|term| crate::command_exists::command_exists(term))
-        .ok_or_else(|| {
-            std::io::Error::new(std::io::ErrorKind::NotFound, "No terminal emulator found")
-        })?;
+    let terminal = find_terminal().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "No terminal emulator found")
+    })?;
 
-    let args = match *terminal {
+    let args = match terminal {
         // Terminals that require "--" before bash -c
         "gnome-terminal"
         | "xfce4-terminal"
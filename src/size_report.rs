@@ -0,0 +1,512 @@
+use crate::command_exists::command_exists;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_NOBITS: u32 = 8;
+const SHT_DYNSYM: u32 = 11;
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const DT_NEEDED: u64 = 1;
+
+struct Section {
+    name: String,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+}
+
+struct Symbol {
+    name: String,
+    size: u64,
+    is_func: bool,
+}
+
+/// The parts of an ELF file the size report needs.
+struct ElfInfo {
+    bits: u8,
+    sections: Vec<Section>,
+    symbols: Vec<Symbol>,
+    needed: Vec<String>,
+}
+
+/// Bounds-checked reads in the file's byte order.
+struct Reader<'a> {
+    data: &'a [u8],
+    little: bool,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, at: u64) -> Option<[u8; N]> {
+        let at = usize::try_from(at).ok()?;
+        self.data.get(at..at.checked_add(N)?)?.try_into().ok()
+    }
+
+    fn u16(&self, at: u64) -> Option<u16> {
+        let b = self.bytes::<2>(at)?;
+        Some(if self.little {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32(&self, at: u64) -> Option<u32> {
+        let b = self.bytes::<4>(at)?;
+        Some(if self.little {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    fn u64(&self, at: u64) -> Option<u64> {
+        let b = self.bytes::<8>(at)?;
+        Some(if self.little {
+            u64::from_le_bytes(b)
+        } else {
+            u64::from_be_bytes(b)
+        })
+    }
+
+    /// A pointer-sized field: 4 bytes in ELF32, 8 in ELF64.
+    fn word(&self, at: u64, wide: bool) -> Option<u64> {
+        if wide {
+            self.u64(at)
+        } else {
+            self.u32(at).map(u64::from)
+        }
+    }
+
+    fn str_at(&self, table: &Section, index: u64) -> String {
+        let start = table.offset.saturating_add(index) as usize;
+        let end = (table.offset.saturating_add(table.size) as usize).min(self.data.len());
+        if start >= end {
+            return String::new();
+        }
+        let bytes = &self.data[start..end];
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..len]).into_owned()
+    }
+}
+
+/// Offset of entry `index` in a table at `base`, or `None` when it overflows or
+/// lies past the end of the file (so small field offsets can be added safely).
+fn entry_offset(data: &[u8], base: u64, index: u64, entry_size: u64) -> Option<u64> {
+    let at = base.checked_add(index.checked_mul(entry_size)?)?;
+    (at <= data.len() as u64).then_some(at)
+}
+
+fn parse_elf(data: &[u8]) -> Option<ElfInfo> {
+    if data.get(..4)? != b"\x7fELF" {
+        return None;
+    }
+    let wide = match data.get(4)? {
+        1 => false,
+        2 => true,
+        _ => return None,
+    };
+    let r = Reader {
+        data,
+        little: *data.get(5)? == 1,
+    };
+
+    let (shoff, shentsize, shnum, shstrndx) = if wide {
+        (r.u64(0x28)?, r.u16(0x3A)?, r.u16(0x3C)?, r.u16(0x3E)?)
+    } else {
+        (
+            u64::from(r.u32(0x20)?),
+            r.u16(0x2E)?,
+            r.u16(0x30)?,
+            r.u16(0x32)?,
+        )
+    };
+
+    let mut sections = Vec::new();
+    let mut name_offsets = Vec::new();
+    for i in 0..u64::from(shnum) {
+        let at = entry_offset(data, shoff, i, u64::from(shentsize))?;
+        let (flags, offset, size, link) = if wide {
+            (
+                r.u64(at + 8)?,
+                r.u64(at + 24)?,
+                r.u64(at + 32)?,
+                r.u32(at + 40)?,
+            )
+        } else {
+            (
+                u64::from(r.u32(at + 8)?),
+                u64::from(r.u32(at + 16)?),
+                u64::from(r.u32(at + 20)?),
+                r.u32(at + 24)?,
+            )
+        };
+        name_offsets.push(r.u32(at)?);
+        sections.push(Section {
+            name: String::new(),
+            kind: r.u32(at + 4)?,
+            flags,
+            offset,
+            size,
+            link,
+        });
+    }
+    // names live in a section of their own, so they are resolved once all headers are read
+    if let Some(table) = sections.get(usize::from(shstrndx)) {
+        let names: Vec<String> = name_offsets
+            .iter()
+            .map(|&off| r.str_at(table, u64::from(off)))
+            .collect();
+        for (section, name) in sections.iter_mut().zip(names) {
+            section.name = name;
+        }
+    }
+
+    // prefer the full symbol table; stripped binaries only have the dynamic one
+    let symtab = sections
+        .iter()
+        .find(|s| s.kind == SHT_SYMTAB)
+        .or_else(|| sections.iter().find(|s| s.kind == SHT_DYNSYM));
+    let mut symbols = Vec::new();
+    if let Some(tab) = symtab
+        && let Some(strtab) = sections.get(tab.link as usize)
+    {
+        let entsize = if wide { 24 } else { 16 };
+        for i in 0..tab.size / entsize {
+            let at = entry_offset(data, tab.offset, i, entsize)?;
+            let (info, size) = if wide {
+                (r.bytes::<1>(at + 4)?[0], r.u64(at + 16)?)
+            } else {
+                (r.bytes::<1>(at + 12)?[0], u64::from(r.u32(at + 8)?))
+            };
+            let kind = info & 0xf;
+            if size == 0 || (kind != STT_FUNC && kind != STT_OBJECT) {
+                continue;
+            }
+            symbols.push(Symbol {
+                name: r.str_at(strtab, u64::from(r.u32(at)?)),
+                size,
+                is_func: kind == STT_FUNC,
+            });
+        }
+    }
+
+    let mut needed = Vec::new();
+    if let Some(dynamic) = sections.iter().find(|s| s.kind == SHT_DYNAMIC)
+        && let Some(strtab) = sections.get(dynamic.link as usize)
+    {
+        let entsize = if wide { 16 } else { 8 };
+        for i in 0..dynamic.size / entsize {
+            let at = entry_offset(data, dynamic.offset, i, entsize)?;
+            let tag = r.word(at, wide)?;
+            if tag == 0 {
+                break;
+            }
+            if tag == DT_NEEDED {
+                needed.push(r.str_at(strtab, r.word(at + entsize / 2, wide)?));
+            }
+        }
+    }
+
+    Some(ElfInfo {
+        bits: if wide { 64 } else { 32 },
+        sections,
+        symbols,
+        needed,
+    })
+}
+
+pub fn human_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+fn signed_size(delta: i64) -> String {
+    let sign = if delta < 0 { "-" } else { "+" };
+    format!("{}{}", sign, human_size(delta.unsigned_abs()))
+}
+
+/// `text`, `data` and `bss` totals the way `size(1)` groups allocated sections.
+fn segment_totals(sections: &[Section]) -> (u64, u64, u64) {
+    let (mut text, mut data, mut bss) = (0, 0, 0);
+    for s in sections.iter().filter(|s| s.flags & SHF_ALLOC != 0) {
+        if s.kind == SHT_NOBITS {
+            bss += s.size;
+        } else if s.flags & SHF_WRITE != 0 {
+            data += s.size;
+        } else {
+            text += s.size;
+        }
+    }
+    (text, data, bss)
+}
+
+/// Demangles C++ names through `c++filt` when it is installed.
+fn demangle(names: Vec<String>) -> Vec<String> {
+    if !names.iter().any(|n| n.starts_with("_Z")) || !command_exists("c++filt") {
+        return names;
+    }
+    let Ok(mut child) = Command::new("c++filt")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    else {
+        return names;
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(names.join("\n").as_bytes());
+    }
+    match child.wait_with_output() {
+        Ok(output) => {
+            let demangled: Vec<String> = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(String::from)
+                .collect();
+            if demangled.len() == names.len() {
+                demangled
+            } else {
+                names
+            }
+        }
+        Err(_) => names,
+    }
+}
+
+fn print_largest(title: &str, symbols: &[&Symbol], limit: usize) {
+    if symbols.is_empty() {
+        return;
+    }
+    println!("\n{}:", title);
+    let shown = &symbols[..symbols.len().min(limit)];
+    let names = demangle(shown.iter().map(|s| s.name.clone()).collect());
+    for (symbol, name) in shown.iter().zip(names) {
+        println!("  {:>10}  {}", human_size(symbol.size), name);
+    }
+}
+
+fn print_elf_report(info: &ElfInfo) -> (u64, u64, u64) {
+    println!("\nSections:");
+    for s in info
+        .sections
+        .iter()
+        .filter(|s| s.flags & SHF_ALLOC != 0 && s.size > 0)
+    {
+        println!("  {:<22} {:>10}", s.name, human_size(s.size));
+    }
+    let (text, data, bss) = segment_totals(&info.sections);
+    println!(
+        "  text {}, data {}, bss {}",
+        human_size(text),
+        human_size(data),
+        human_size(bss)
+    );
+
+    let mut symbols: Vec<&Symbol> = info.symbols.iter().collect();
+    // a name can have several symbols (statics in different files, weak and
+    // strong copies); keep the largest, which needs equal names next to each other
+    symbols.sort_by(|a, b| a.name.cmp(&b.name).then(b.size.cmp(&a.size)));
+    symbols.dedup_by(|a, b| a.name == b.name);
+    symbols.sort_by(|a, b| b.size.cmp(&a.size).then(a.name.cmp(&b.name)));
+    let functions: Vec<&Symbol> = symbols.iter().copied().filter(|s| s.is_func).collect();
+    let objects: Vec<&Symbol> = symbols.iter().copied().filter(|s| !s.is_func).collect();
+    print_largest("Largest functions", &functions, 10);
+    print_largest("Largest objects", &objects, 10);
+
+    println!("\nShared libraries:");
+    if info.needed.is_empty() {
+        println!("  (none, statically linked)");
+    }
+    for lib in &info.needed {
+        println!("  {}", lib);
+    }
+    (text, data, bss)
+}
+
+/// Shows what the installed tools say about a non-ELF binary (PE, Mach-O).
+fn print_tool_report(exe: &str) {
+    let mut ran = false;
+    let tools: [(&str, &[&str]); 3] = [("size", &[]), ("otool", &["-L"]), ("objdump", &["-p"])];
+    for (tool, args) in tools {
+        if !command_exists(tool) {
+            continue;
+        }
+        let Ok(output) = Command::new(tool).args(args).arg(exe).output() else {
+            continue;
+        };
+        if !output.status.success() {
+            continue;
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        // objdump -p is long; only the imported DLLs are of interest here
+        let lines: Vec<&str> = if tool == "objdump" {
+            text.lines().filter(|l| l.contains("DLL Name")).collect()
+        } else {
+            text.lines().collect()
+        };
+        if lines.is_empty() {
+            continue;
+        }
+        println!("\n{} {}:", tool, args.join(" "));
+        for line in lines {
+            println!("  {}", line.trim());
+        }
+        ran = true;
+    }
+    if !ran {
+        println!("\nNot an ELF file and no size/otool/objdump found for a detailed report.");
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn ago(secs: u64) -> String {
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{} min ago", secs / 60),
+        3600..86400 => format!("{} h ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86400),
+    }
+}
+
+/// One line of `.crun/size-history`: "<exe>\t<mtime>\t<recorded>\t<file>\t<text>\t<data>\t<bss>".
+struct HistoryEntry {
+    exe: String,
+    mtime: u128,
+    recorded: u64,
+    sizes: [u64; 4],
+}
+
+fn read_history(file: &Path) -> Vec<HistoryEntry> {
+    fs::read_to_string(file)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [exe, mtime, recorded, file, text, data, bss] = fields[..] else {
+                return None;
+            };
+            Some(HistoryEntry {
+                exe: exe.to_string(),
+                mtime: mtime.parse().ok()?,
+                recorded: recorded.parse().ok()?,
+                sizes: [
+                    file.parse().ok()?,
+                    text.parse().ok()?,
+                    data.parse().ok()?,
+                    bss.parse().ok()?,
+                ],
+            })
+        })
+        .collect()
+}
+
+/// Appends this build to the history (once per build, going by the binary's
+/// mtime) and prints the recent entries with the growth between them.
+fn update_history(build_dir: &str, exe: &str, sizes: [u64; 4]) {
+    let file = Path::new(build_dir).join("size-history");
+    let name = Path::new(exe)
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mtime = fs::metadata(exe)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+
+    let mut history: Vec<HistoryEntry> = read_history(&file)
+        .into_iter()
+        .filter(|e| e.exe == name)
+        .collect();
+    if history.last().is_none_or(|last| last.mtime != mtime) {
+        let entry = HistoryEntry {
+            exe: name.clone(),
+            mtime,
+            recorded: unix_now(),
+            sizes,
+        };
+        let line = format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            entry.exe, entry.mtime, entry.recorded, sizes[0], sizes[1], sizes[2], sizes[3]
+        );
+        if let Ok(mut f) = fs::OpenOptions::new().create(true).append(true).open(&file) {
+            let _ = f.write_all(line.as_bytes());
+        }
+        history.push(entry);
+    }
+
+    if history.len() < 2 {
+        println!(
+            "\nHistory: first recorded build of {} (kept in {})",
+            name,
+            file.display()
+        );
+        return;
+    }
+    println!("\nHistory ({}):", file.display());
+    let now = unix_now();
+    let start = history.len().saturating_sub(5);
+    for (i, entry) in history.iter().enumerate().skip(start) {
+        let delta = match i.checked_sub(1).and_then(|p| history.get(p)) {
+            Some(prev) => format!(
+                " ({}, text {})",
+                signed_size(entry.sizes[0] as i64 - prev.sizes[0] as i64),
+                signed_size(entry.sizes[1] as i64 - prev.sizes[1] as i64)
+            ),
+            None => String::new(),
+        };
+        println!(
+            "  {:<12} {:>10}  text {:>10}{}",
+            ago(now.saturating_sub(entry.recorded)),
+            human_size(entry.sizes[0]),
+            human_size(entry.sizes[1]),
+            delta
+        );
+    }
+}
+
+/// Prints the size report for a built executable: sections, largest symbols,
+/// shared libraries and how the size changed over previous builds.
+pub fn report(exe: &str, build_dir: &str) -> Result<(), String> {
+    let data = fs::read(exe).map_err(|e| format!("Cannot read {}: {}", exe, e))?;
+    let file_size = data.len() as u64;
+
+    let sizes = match parse_elf(&data) {
+        Some(info) => {
+            println!(
+                "Binary: {} ({}, ELF {}-bit)",
+                exe,
+                human_size(file_size),
+                info.bits
+            );
+            let (text, data, bss) = print_elf_report(&info);
+            [file_size, text, data, bss]
+        }
+        None => {
+            println!("Binary: {} ({})", exe, human_size(file_size));
+            print_tool_report(exe);
+            [file_size, 0, 0, 0]
+        }
+    };
+    update_history(build_dir, exe, sizes);
+    Ok(())
+}
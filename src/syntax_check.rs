@@ -0,0 +1,96 @@
+use crate::compiler_registry::{self, Family};
+use crate::{LOG, emit, run_command};
+use std::fs;
+use std::path::Path;
+
+pub const HEADER_EXTENSIONS: &[&str] = &["h", "hpp", "hh", "hxx"];
+
+pub fn is_header(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| HEADER_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Whether a `.h` file is C++: it is when it uses constructs C does not have.
+fn looks_like_cpp(content: &str) -> bool {
+    content.lines().map(str::trim_start).any(|line| {
+        [
+            "namespace ",
+            "template<",
+            "template <",
+            "class ",
+            "#include <iostream>",
+        ]
+        .iter()
+        .any(|k| line.starts_with(k))
+            || line.contains("std::")
+    })
+}
+
+/// The file to hand to the compiler for `src`. Sources are checked as they
+/// are; a header is wrapped in `.crun/check-<name>.c` (or `.cpp`) that only
+/// includes it, since compiling a header directly produces a PCH instead.
+pub fn translation_unit(src: &str, build_dir: &str) -> Result<String, String> {
+    if !is_header(src) {
+        return Ok(src.to_string());
+    }
+    let header = fs::canonicalize(src).map_err(|e| format!("Cannot read {}: {}", src, e))?;
+    let content = fs::read_to_string(&header).unwrap_or_default();
+    let is_cpp = !src.to_ascii_lowercase().ends_with(".h") || looks_like_cpp(&content);
+    let name = header
+        .file_name()
+        .map(|n| n.to_string_lossy().replace('.', "_"))
+        .unwrap_or_default();
+    fs::create_dir_all(build_dir).map_err(|e| format!("Cannot create {}: {}", build_dir, e))?;
+    let unit = Path::new(build_dir).join(format!(
+        "check-{}.{}",
+        name,
+        if is_cpp { "cpp" } else { "c" }
+    ));
+    fs::write(&unit, format!("#include \"{}\"\n", header.display()))
+        .map_err(|e| format!("Cannot write {}: {}", unit.display(), e))?;
+    Ok(unit.to_string_lossy().to_string())
+}
+
+fn syntax_only_flag(family: Family) -> Option<&'static str> {
+    match family {
+        Family::Gcc | Family::Clang | Family::Zig | Family::Intel => Some("-fsyntax-only"),
+        Family::Msvc => Some("/Zs"),
+        _ => None,
+    }
+}
+
+/// Parses and type-checks `source` without generating code, linking or
+/// running anything. Compilers without a syntax-only mode compile to a
+/// throwaway object instead.
+pub fn check(compiler: &str, source: &str, extra: &str, build_dir: &str) -> Result<(), String> {
+    let def = compiler_registry::definition_for(compiler);
+    let ok = match syntax_only_flag(def.family) {
+        Some(flag) => {
+            let mut args = def.prefix_args.clone();
+            args.extend(def.default_args.iter().cloned());
+            args.extend(extra.split_whitespace().map(String::from));
+            args.push(flag.to_string());
+            args.push(source.to_string());
+            let arg_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+            run_command(compiler_registry::command_for(compiler), &arg_refs)
+        }
+        None => {
+            LOG.lock().unwrap().detail(&format_args!(
+                "{} has no syntax-only mode, compiling to a throwaway object",
+                compiler
+            ));
+            let object = Path::new(build_dir).join("check.o");
+            let object = object.to_string_lossy();
+            let ok = emit::compile_object(compiler, source, extra, &object).is_ok();
+            let _ = fs::remove_file(&*object);
+            ok
+        }
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err("Syntax check failed".to_string())
+    }
+}
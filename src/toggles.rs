@@ -0,0 +1,218 @@
+use crate::build_env::Setting;
+use crate::capabilities::{self, cached_capabilities};
+use crate::command_exists::command_exists;
+use crate::compiler_registry::{self, Family, Lang};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Warnings {
+    None,
+    Default,
+    All,
+    Extra,
+    Pedantic,
+}
+
+impl Warnings {
+    pub fn parse(text: &str) -> Option<Warnings> {
+        match text {
+            "none" | "off" => Some(Warnings::None),
+            "default" => Some(Warnings::Default),
+            "all" => Some(Warnings::All),
+            "extra" => Some(Warnings::Extra),
+            "pedantic" => Some(Warnings::Pedantic),
+            _ => None,
+        }
+    }
+}
+
+/// The high-level switches (`--warnings`, `--werror`, `--threads`, ...) given
+/// on the command line.
+#[derive(Clone, Debug, Default)]
+pub struct Toggles {
+    pub warnings: Option<Warnings>,
+    pub werror: bool,
+    pub threads: bool,
+    pub openmp: bool,
+    pub static_link: bool,
+    pub lto: bool,
+    pub debug_info: bool,
+}
+
+impl Toggles {
+    /// Capability features the toggles depend on, as recorded by the probe.
+    fn required_features(&self) -> Vec<&'static str> {
+        [
+            (self.threads, "threads"),
+            (self.openmp, "openmp"),
+            (self.static_link, "static"),
+        ]
+        .into_iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, feature)| feature)
+        .collect()
+    }
+}
+
+fn is_gcc_like(family: Family) -> bool {
+    matches!(
+        family,
+        Family::Gcc | Family::Clang | Family::Zig | Family::Intel
+    )
+}
+
+fn warning_flags(family: Family, level: Warnings) -> Option<&'static str> {
+    match (family, level) {
+        (_, Warnings::Default) => Some(""),
+        (Family::Msvc, Warnings::None) => Some("/W0"),
+        (Family::Msvc, Warnings::All) => Some("/W3"),
+        (Family::Msvc, Warnings::Extra) => Some("/W4"),
+        (Family::Msvc, Warnings::Pedantic) => Some("/W4 /permissive-"),
+        (Family::Tcc, Warnings::None) => Some("-w"),
+        (Family::Tcc, Warnings::All) => Some("-Wall"),
+        (f, Warnings::None) if is_gcc_like(f) => Some("-w"),
+        (f, Warnings::All) if is_gcc_like(f) => Some("-Wall"),
+        (f, Warnings::Extra) if is_gcc_like(f) => Some("-Wall -Wextra"),
+        (f, Warnings::Pedantic) if is_gcc_like(f) => Some("-Wall -Wextra -Wpedantic"),
+        _ => None,
+    }
+}
+
+fn werror_flag(family: Family) -> Option<&'static str> {
+    match family {
+        Family::Msvc => Some("/WX"),
+        f if is_gcc_like(f) || f == Family::Tcc => Some("-Werror"),
+        _ => None,
+    }
+}
+
+/// Compile and link flags for LTO; cl needs whole-program optimization on both sides.
+fn lto_flags(family: Family) -> Option<(&'static str, &'static str)> {
+    match family {
+        Family::Msvc => Some(("/GL", "/LTCG")),
+        f if is_gcc_like(f) => Some(("-flto", "")),
+        _ => None,
+    }
+}
+
+fn debug_info_flags(family: Family) -> Option<(&'static str, &'static str)> {
+    match family {
+        Family::Msvc => Some(("/Zi", "/DEBUG")),
+        f if is_gcc_like(f) || f == Family::Tcc => Some(("-g", "")),
+        _ => None,
+    }
+}
+
+fn unsupported(toggle: &str, compiler: &str, family: Family) -> String {
+    format!(
+        "{} is not supported by {} ({} family); use -e with the compiler's own flags or pick another compiler with -c",
+        toggle,
+        compiler,
+        family.as_str()
+    )
+}
+
+/// Whether the cached capabilities of `compiler` cover `required`, rechecking
+/// once when they do not; `None` when it was never probed.
+fn supports_all(compiler: &str, lang: Lang, required: &[&str]) -> Option<bool> {
+    let caps = cached_capabilities(compiler, lang)?;
+    if required.iter().all(|f| caps.supports(f)) {
+        return Some(true);
+    }
+    let caps = capabilities::recheck(compiler, lang);
+    Some(required.iter().all(|f| caps.supports(f)))
+}
+
+/// Fails when a capability probe found that `compiler` cannot build with one
+/// of the toggles (e.g. OpenMP without libgomp installed).
+fn check_capabilities(compiler: &str, lang: Lang, toggles: &Toggles) -> Result<(), String> {
+    let required = toggles.required_features();
+    if supports_all(compiler, lang, &required) != Some(false) {
+        return Ok(());
+    }
+    let caps = capabilities::recheck(compiler, lang);
+    match required.into_iter().find(|f| !caps.supports(f)) {
+        Some(feature) => Err(format!(
+            "{} could not build a test program with --{}; install the missing runtime (crun -check lists what is supported, crun --refresh re-probes everything) or pick another compiler with -c",
+            compiler, feature
+        )),
+        None => Ok(()),
+    }
+}
+
+/// With auto-detection, switches to an installed compiler whose cached
+/// capabilities cover the toggles when the detected one is known to lack them.
+pub fn prefer_capable(compiler: &str, lang: Lang, toggles: &Toggles) -> Option<String> {
+    let required = toggles.required_features();
+    if required.is_empty() || supports_all(compiler, lang, &required) != Some(false) {
+        return None;
+    }
+    compiler_registry::candidate_names(lang)
+        .into_iter()
+        .filter(|name| *name != compiler)
+        .find(|name| {
+            command_exists(compiler_registry::command_for(name))
+                && supports_all(name, lang, &required) == Some(true)
+        })
+        .map(String::from)
+}
+
+/// Translates `toggles` into `compiler`'s flags: compile flags and link flags
+/// (the latter go after the source, behind `/link` for cl). Toggles the
+/// compiler family has no equivalent for are an error rather than ignored.
+pub fn toggle_flags(
+    compiler: &str,
+    lang: Lang,
+    toggles: &Toggles,
+) -> Result<(Vec<Setting>, Vec<Setting>), String> {
+    let family = compiler_registry::family_of(compiler);
+    check_capabilities(compiler, lang, toggles)?;
+
+    let mut compile = Vec::new();
+    let mut link = Vec::new();
+    let add = |list: &mut Vec<Setting>, value: &str, source: &str| {
+        if !value.is_empty() {
+            list.push(Setting {
+                value: value.to_string(),
+                source: source.to_string(),
+            });
+        }
+    };
+
+    if let Some(level) = toggles.warnings {
+        let flags = warning_flags(family, level)
+            .ok_or_else(|| unsupported("--warnings", compiler, family))?;
+        add(&mut compile, flags, "--warnings");
+    }
+    if toggles.werror {
+        let flag = werror_flag(family).ok_or_else(|| unsupported("--werror", compiler, family))?;
+        add(&mut compile, flag, "--werror");
+    }
+    if toggles.threads {
+        // cl links the threading runtime by default, so its flag is empty
+        let flag = capabilities::threads_flag(family)
+            .ok_or_else(|| unsupported("--threads", compiler, family))?;
+        add(&mut compile, flag, "--threads");
+    }
+    if toggles.openmp {
+        let flag = capabilities::openmp_flag(family)
+            .ok_or_else(|| unsupported("--openmp", compiler, family))?;
+        add(&mut compile, flag, "--openmp");
+    }
+    if toggles.static_link {
+        let flag = capabilities::static_flag(family)
+            .ok_or_else(|| unsupported("--static", compiler, family))?;
+        add(&mut compile, flag, "--static");
+    }
+    if toggles.lto {
+        let (c, l) = lto_flags(family).ok_or_else(|| unsupported("--lto", compiler, family))?;
+        add(&mut compile, c, "--lto");
+        add(&mut link, l, "--lto");
+    }
+    if toggles.debug_info {
+        let (c, l) = debug_info_flags(family)
+            .ok_or_else(|| unsupported("--debug-info", compiler, family))?;
+        add(&mut compile, c, "--debug-info");
+        add(&mut link, l, "--debug-info");
+    }
+    Ok((compile, link))
+}
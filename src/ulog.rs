@@ -4,11 +4,31 @@ use std::io::Write;
 
 pub struct Ulog {
     count: usize,
+    verbose: bool,
 }
 
 impl Ulog {
     pub(crate) fn new() -> Self {
-        Ulog { count: 0 }
+        Ulog {
+            count: 0,
+            verbose: false,
+        }
+    }
+
+    /// In verbose mode the log is never cleared and [`Ulog::detail`] lines are shown.
+    pub(crate) fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    pub(crate) fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    /// Prints a line only in verbose mode.
+    pub(crate) fn detail(&mut self, format: &Arguments) {
+        if self.verbose {
+            self.println(format, None);
+        }
     }
 
     pub(crate) fn println(&mut self, format: &Arguments, args: Option<&[&dyn std::fmt::Display]>) {
@@ -26,7 +46,7 @@ impl Ulog {
     }
 
     pub(crate) fn clear(&mut self) {
-        if self.count > 0 {
+        if self.count > 0 && !self.verbose {
             clear_last_lines(self.count);
             self.count = 0;
         }